@@ -45,6 +45,12 @@ pub fn init_heap(
     Ok(())
 }
 
+/// Heap usage in bytes: (used, peak, total)
+pub fn heap_stats() -> (usize, usize, usize) {
+    let allocator = ALLOCATOR.lock();
+    (allocator.used(), allocator.peak(), HEAP_SIZE)
+}
+
 pub struct Dummy;
 
 unsafe impl GlobalAlloc for Dummy {
@@ -26,6 +26,8 @@ struct ListNode {
 pub struct FixedSizeBlockAllocator {
     list_heads: [Option<&'static mut ListNode>; BLOCK_SIZES.len()],
     fallback_allocator: linked_list_allocator::Heap,
+    used: usize, // bytes currently handed out, counted in whole blocks
+    peak: usize, // high-water mark of `used`
 }
 
 impl FixedSizeBlockAllocator {
@@ -35,6 +37,26 @@ impl FixedSizeBlockAllocator {
         FixedSizeBlockAllocator {
             list_heads: [EMPTY; BLOCK_SIZES.len()],
             fallback_allocator: linked_list_allocator::Heap::empty(),
+            used: 0,
+            peak: 0,
+        }
+    }
+
+    /// Bytes currently allocated.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Highest number of bytes allocated at once since boot.
+    pub fn peak(&self) -> usize {
+        self.peak
+    }
+
+    /// Size accounted for an allocation with the given layout.
+    fn accounted_size(layout: &Layout) -> usize {
+        match list_index(layout) {
+            Some(index) => BLOCK_SIZES[index],
+            None => layout.size(),
         }
     }
 
@@ -61,7 +83,7 @@ impl FixedSizeBlockAllocator {
 unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
     unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
         let mut allocator = self.lock();
-        match list_index(&layout) {
+        let ptr = match list_index(&layout) {
             Some(index) => {
                 match allocator.list_heads[index].take() {
                     Some(node) => {
@@ -79,11 +101,17 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
                 }
             }
             None => allocator.fallback_alloc(layout),
+        };
+        if !ptr.is_null() {
+            allocator.used += FixedSizeBlockAllocator::accounted_size(&layout);
+            allocator.peak = allocator.peak.max(allocator.used);
         }
+        ptr
     }
 
     unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
         let mut allocator = self.lock();
+        allocator.used -= FixedSizeBlockAllocator::accounted_size(&layout);
         match list_index(&layout) {
             Some(index) => {
                 let new_node = ListNode {
@@ -107,3 +135,55 @@ unsafe impl GlobalAlloc for Locked<FixedSizeBlockAllocator> {
         }
     }
 }
+
+/// Backing memory for the allocator tests
+#[cfg(test)]
+#[repr(align(4096))]
+struct Arena([u8; 8192]);
+
+#[test_case]
+fn test_used_and_peak() {
+    static mut ARENA: Arena = Arena([0; 8192]);
+    let heap = Locked::new(FixedSizeBlockAllocator::new());
+    unsafe { heap.lock().init(ptr::addr_of_mut!(ARENA.0) as usize, mem::size_of::<Arena>()) };
+
+    let small = Layout::from_size_align(10, 1).unwrap();
+    let medium = Layout::from_size_align(100, 8).unwrap();
+    let large = Layout::from_size_align(3000, 8).unwrap();
+    unsafe {
+        // Block-sized allocations count as the whole block
+        let a = heap.alloc(small);
+        let b = heap.alloc(medium);
+        assert_eq!(heap.lock().used(), 16 + 128);
+        heap.dealloc(a, small);
+        assert_eq!(heap.lock().used(), 128);
+
+        // A block reused from the free list is counted again
+        let c = heap.alloc(small);
+        assert_eq!(heap.lock().used(), 16 + 128);
+
+        // Larger allocations go to the fallback and count their exact size
+        let d = heap.alloc(large);
+        assert!(!d.is_null());
+        assert_eq!(heap.lock().used(), 16 + 128 + 3000);
+
+        heap.dealloc(b, medium);
+        heap.dealloc(c, small);
+        heap.dealloc(d, large);
+    }
+    assert_eq!(heap.lock().used(), 0);
+    assert_eq!(heap.lock().peak(), 16 + 128 + 3000);
+}
+
+#[test_case]
+fn test_failed_alloc_not_counted() {
+    static mut ARENA: Arena = Arena([0; 8192]);
+    let heap = Locked::new(FixedSizeBlockAllocator::new());
+    unsafe { heap.lock().init(ptr::addr_of_mut!(ARENA.0) as usize, mem::size_of::<Arena>()) };
+
+    let too_big = Layout::from_size_align(64 * 1024, 8).unwrap();
+    let ptr = unsafe { heap.alloc(too_big) };
+    assert!(ptr.is_null());
+    assert_eq!(heap.lock().used(), 0);
+    assert_eq!(heap.lock().peak(), 0);
+}
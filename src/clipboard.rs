@@ -0,0 +1,22 @@
+use alloc::{string::String, vec::Vec};
+use spin::Mutex;
+
+/// Lines copied with `clip`, shared by everything that reads or sets the clipboard
+static CLIPBOARD: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Replace the clipboard with the lines of `text`
+pub fn set(text: &str) -> usize {
+    let mut clipboard = CLIPBOARD.lock();
+    *clipboard = text.lines().map(String::from).collect();
+    clipboard.len()
+}
+
+/// All lines on the clipboard
+pub fn lines() -> Vec<String> {
+    CLIPBOARD.lock().clone()
+}
+
+/// The first line on the clipboard, if any
+pub fn first_line() -> Option<String> {
+    CLIPBOARD.lock().first().cloned()
+}
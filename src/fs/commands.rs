@@ -1,10 +1,10 @@
 use super::storage::{ROOT_DIR};
 use super::file::File;
-use super::dir::Directory;
-use crate::repl::Terminal;
-use crate::alloc::string::ToString;
+use super::dir::{Directory, intern};
+use crate::repl::{Output, Terminal};
+use crate::vga_buffer::{Color, ColorCode};
 
-use alloc::{boxed::Box, format, string::String, vec, vec::Vec};
+use alloc::{format, string::String, vec, vec::Vec};
 
 /// Create a new file or folder and persist changes
 pub fn spawn_file_folder(term: &mut Terminal, parent_dir: &mut Directory, name: &str) {
@@ -13,6 +13,25 @@ pub fn spawn_file_folder(term: &mut Terminal, parent_dir: &mut Directory, name:
         return;
     }
 
+    // Re-creating an entry would replace it, so it's subject to the same lock checks as `del`
+    if let Some(file) = parent_dir.get_file(name) {
+        if file.locked {
+            term.write_str(&format!("'{}' is locked, unlock it first\n", name));
+        } else {
+            term.write_str(&format!("'{}' already exists\n", name));
+        }
+        return;
+    }
+    if let Some(dir) = parent_dir.subdirs.get(name) {
+        let locked = count_locked(dir);
+        if locked > 0 {
+            term.write_str(&format!("'{}' contains {} locked file(s)\n", name, locked));
+        } else {
+            term.write_str(&format!("'{}' already exists\n", name));
+        }
+        return;
+    }
+
     if name.contains('.') {
         // Treat as file
         let file = File::new(name);
@@ -20,36 +39,147 @@ pub fn spawn_file_folder(term: &mut Terminal, parent_dir: &mut Directory, name:
         term.write_str(&format!("Created file '{}'\n", name));
     } else {
         // Treat as folder
-        let static_name: &'static str = Box::leak(name.to_string().into_boxed_str());
-
-        let dir = Directory::new(static_name);
+        let dir = Directory::new(intern(name));
         parent_dir.add_subdir(dir);
         term.write_str(&format!("Created folder '{}'\n", name));
     }
 }
 
-/// Delete a file or folder and persist changes
-pub fn despawn_file_folder(term: &mut Terminal, parent_dir: &mut Directory, name: &str) {
-    if name.is_empty() {
+/// Create a chain of folders like `a/b/c` below `cwd`, reusing existing ones
+pub fn make_dirs(term: &mut Terminal, cwd: &mut Directory, path: &str) {
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+    if parts.is_empty() {
         term.write_str("Name cannot be empty!\n");
         return;
     }
+    if let Some(bad) = parts.iter().find(|p| p.contains('.')) {
+        term.write_str(&format!("Folder names cannot contain a '.': '{}'\n", bad));
+        return;
+    }
+
+    let mut created = 0;
+    ensure_path(cwd, &parts, &mut created);
+    term.write_str(&format!("Created {} folder(s)\n", created));
+}
+
+/// Walk `parts` below `dir`, creating missing folders and counting them in `created`
+fn ensure_path<'a>(dir: &'a mut Directory, parts: &[&str], created: &mut usize) -> &'a mut Directory {
+    let mut dir = dir;
+    for part in parts {
+        if !dir.subdirs.contains_key(part) {
+            dir.add_subdir(Directory::new(intern(part)));
+            *created += 1;
+        }
+        dir = dir.get_subdir_mut(part).unwrap();
+    }
+    dir
+}
+
+/// Create an empty file, or update the modification time of an existing one
+pub fn touch_file(term: &mut Terminal, parent_dir: &mut Directory, name: &str) {
+    if let Some(file) = parent_dir.files.get_mut(name) {
+        if file.locked {
+            term.write_str(&format!("'{}' is locked\n", name));
+        } else {
+            file.touch();
+        }
+    } else if parent_dir.subdirs.contains_key(name) {
+        term.write_str(&format!("'{}' is a folder\n", name));
+    } else if !name.contains('.') {
+        term.write_str("File names need a '.', e.g. notes.txt\n");
+    } else {
+        parent_dir.add_file(File::new(name));
+    }
+}
+
+/// A file or folder taken out of the tree
+pub enum Entry {
+    File(File),
+    Dir(Directory),
+}
+
+/// Remove a file or folder, returning it so the caller can move it to the trash.
+///
+/// Non-empty folders are only removed with `recursive` (`del -r`), which
+/// reports how much was removed.
+pub fn despawn_file_folder(term: &mut Terminal, parent_dir: &mut Directory, name: &str, recursive: bool) -> Option<Entry> {
+    if name.is_empty() {
+        term.write_str("Name cannot be empty!\n");
+        return None;
+    }
+
+    if let Some(file) = parent_dir.get_file(name) {
+        if file.locked {
+            term.write_str(&format!("'{}' is locked, unlock it first\n", name));
+            return None;
+        }
+    }
+    if let Some(dir) = parent_dir.subdirs.get(name) {
+        let locked = count_locked(dir);
+        if locked > 0 {
+            term.write_str(&format!("'{}' contains {} locked file(s)\n", name, locked));
+            return None;
+        }
+    }
+
+    // Files given to `del -r` fall through and are deleted the normal way
+    if recursive {
+        if let Some(dir) = parent_dir.remove_subdir(name) {
+            let (files, dirs) = count_entries(&dir);
+            term.write_str(&format!("Moved '{}' to trash ({} files, {} folders)\n", name, files, dirs + 1));
+            return Some(Entry::Dir(dir));
+        }
+    }
+
+    if let Some(dir) = parent_dir.subdirs.get(name) {
+        if !dir.files.is_empty() || !dir.subdirs.is_empty() {
+            term.write_str("directory not empty, use del -r\n");
+            return None;
+        }
+    }
 
     let removed = if name.contains('.') {
-        parent_dir.remove_file(name).is_some()
+        parent_dir.remove_file(name).map(Entry::File)
     } else {
-        parent_dir.remove_subdir(name).is_some()
+        parent_dir.remove_subdir(name).map(Entry::Dir)
     };
 
-    if removed {
-        term.write_str(&format!("Deleted '{}'\n", name));
+    if removed.is_some() {
+        term.write_str(&format!("Moved '{}' to trash\n", name));
     } else {
         term.write_str(&format!("'{}' not found\n", name));
     }
+    removed
+}
+
+/// Count locked files in `dir` and its subfolders
+fn count_locked(dir: &Directory) -> usize {
+    dir.files.values().filter(|f| f.locked).count()
+        + dir.subdirs.values().map(|sub| count_locked(sub)).sum::<usize>()
 }
 
-/// Scan files and directories
-pub fn scan_files(term: &mut Terminal, root: &Directory, cwd_path: &[&str], path: Option<&str>) {
+/// Set or clear the locked flag of a file
+pub fn set_locked(term: &mut Terminal, dir: &mut Directory, name: &str, locked: bool) {
+    match dir.files.get_mut(name) {
+        Some(file) => file.locked = locked,
+        None => term.write_str(&format!("File '{}' not found\n", name)),
+    }
+}
+
+/// Count all files and folders below `dir` (not including `dir` itself)
+fn count_entries(dir: &Directory) -> (usize, usize) {
+    let mut files = dir.files.len();
+    let mut dirs = dir.subdirs.len();
+    for sub in dir.subdirs.values() {
+        let (f, d) = count_entries(sub);
+        files += f;
+        dirs += d;
+    }
+    (files, dirs)
+}
+
+/// Scan files and directories; `long` lists one entry per line with timestamps
+pub fn scan_files(term: &mut Terminal, root: &Directory, cwd_path: &[&str], path: Option<&str>, long: bool) {
     // Determine target directory
     let mut temp: &Directory;
     let mut path_stack = vec![root.name.clone()];
@@ -85,22 +215,1237 @@ pub fn scan_files(term: &mut Terminal, root: &Directory, cwd_path: &[&str], path
         temp = temp_ref;
     }
 
+    // Entries starting with '.' are hidden
+    let subdirs: Vec<String> = temp.list_subdirs().into_iter().filter(|d| !d.starts_with('.')).collect();
+    let files: Vec<String> = temp.list_files().into_iter().filter(|f| !f.starts_with('.')).collect();
+
+    if long {
+        term.write_str(&format!("/{}\n", path_stack.join("/")));
+        for d in subdirs.iter() {
+            term.write_str(&format!("{:<37}/{}\n", "", d));
+        }
+        for f in files.iter() {
+            let file = temp.get_file(f).unwrap();
+            let (ch, cm, cs) = crate::sys::ticks_to_hms(file.created_ticks);
+            let (mh, mm, ms) = crate::sys::ticks_to_hms(file.modified_ticks);
+            term.write_str(&format!(
+                "created {:02}:{:02}:{:02}  modified {:02}:{:02}:{:02}  {}\n",
+                ch, cm, cs, mh, mm, ms, f
+            ));
+        }
+        if subdirs.is_empty() && files.is_empty() {
+            term.write_str("(empty)\n");
+        }
+        return;
+    }
+
     // Show path
     term.write_str(&format!("/{} -> ", path_stack.join("/")));
 
     // Print folders
-    for d in temp.list_subdirs() {
+    for d in subdirs.iter() {
         term.write_str(&format!("/{0} ", d));
     }
 
     // Print files
-    for f in temp.list_files() {
+    for f in files.iter() {
         term.write_str(&format!("{} ", f));
     }
 
-    if temp.subdirs.is_empty() && temp.files.is_empty() {
+    if subdirs.is_empty() && files.is_empty() {
         term.write_str("(empty)");
     }
 
     term.write_char('\n');
 }
+
+/// Rename a file or folder, or move it into an existing folder
+pub fn move_file_folder(term: &mut Terminal, parent_dir: &mut Directory, src: &str, dst: &str) {
+    if src.is_empty() || dst.is_empty() {
+        term.write_str("Name cannot be empty!\n");
+        return;
+    }
+
+    let src_is_file = parent_dir.files.contains_key(src);
+    if !src_is_file && !parent_dir.subdirs.contains_key(src) {
+        term.write_str(&format!("'{}' not found\n", src));
+        return;
+    }
+    if parent_dir.get_file(src).is_some_and(|f| f.locked) {
+        term.write_str(&format!("'{}' is locked\n", src));
+        return;
+    }
+
+    // Destination is an existing folder: move source into it
+    if let Some(target) = parent_dir.subdirs.get(dst) {
+        if src == dst {
+            term.write_str(&format!("Cannot move '{}' into itself\n", src));
+            return;
+        }
+        if target.files.contains_key(src) || target.subdirs.contains_key(src) {
+            term.write_str(&format!("'{}/{}' already exists\n", dst, src));
+            return;
+        }
+
+        if src_is_file {
+            let file = parent_dir.remove_file(src).unwrap();
+            parent_dir.get_subdir_mut(dst).unwrap().add_file(file);
+        } else {
+            let dir = parent_dir.remove_subdir(src).unwrap();
+            parent_dir.get_subdir_mut(dst).unwrap().add_subdir(dir);
+        }
+        term.write_str(&format!("Moved '{}' into '{}'\n", src, dst));
+        return;
+    }
+
+    // Otherwise rename in place, keeping the file/folder naming convention
+    if parent_dir.files.contains_key(dst) {
+        term.write_str(&format!("'{}' already exists\n", dst));
+        return;
+    }
+    if src_is_file != dst.contains('.') {
+        if src_is_file {
+            term.write_str("File names must contain a '.'\n");
+        } else {
+            term.write_str("Folder names cannot contain a '.'\n");
+        }
+        return;
+    }
+
+    let renamed = if src_is_file {
+        parent_dir.rename_file(src, dst)
+    } else {
+        parent_dir.rename_subdir(src, dst)
+    };
+    if renamed {
+        term.write_str(&format!("Renamed '{}' to '{}'\n", src, dst));
+    } else {
+        term.write_str(&format!("Could not rename '{}' to '{}'\n", src, dst));
+    }
+}
+
+/// Copy a file or folder (folders are copied recursively)
+pub fn copy_file_folder(term: &mut Terminal, parent_dir: &mut Directory, src: &str, dst: &str) {
+    if src.is_empty() || dst.is_empty() {
+        term.write_str("Name cannot be empty!\n");
+        return;
+    }
+
+    let src_is_file = parent_dir.files.contains_key(src);
+    if !src_is_file && !parent_dir.subdirs.contains_key(src) {
+        term.write_str(&format!("'{}' not found\n", src));
+        return;
+    }
+
+    // Destination is an existing folder: copy source into it
+    if let Some(target) = parent_dir.subdirs.get(dst) {
+        if src == dst {
+            term.write_str(&format!("Cannot copy '{}' into itself\n", src));
+            return;
+        }
+        if target.files.contains_key(src) || target.subdirs.contains_key(src) {
+            term.write_str(&format!("'{}/{}' already exists\n", dst, src));
+            return;
+        }
+
+        if src_is_file {
+            let file = copy_file(parent_dir.get_file(src).unwrap(), src);
+            parent_dir.get_subdir_mut(dst).unwrap().add_file(file);
+        } else {
+            let dir = copy_dir(parent_dir.subdirs.get(src).unwrap(), intern(src));
+            parent_dir.get_subdir_mut(dst).unwrap().add_subdir(dir);
+        }
+        term.write_str(&format!("Copied '{}' into '{}'\n", src, dst));
+        return;
+    }
+
+    if parent_dir.files.contains_key(dst) {
+        if src_is_file {
+            term.write_str(&format!("'{}' already exists\n", dst));
+        } else {
+            term.write_str(&format!("Cannot copy folder '{}' onto file '{}'\n", src, dst));
+        }
+        return;
+    }
+    if src_is_file != dst.contains('.') {
+        if src_is_file {
+            term.write_str("File names must contain a '.'\n");
+        } else {
+            term.write_str("Folder names cannot contain a '.'\n");
+        }
+        return;
+    }
+
+    if src_is_file {
+        let file = copy_file(parent_dir.get_file(src).unwrap(), dst);
+        parent_dir.add_file(file);
+    } else {
+        let dir = copy_dir(parent_dir.subdirs.get(src).unwrap(), intern(dst));
+        parent_dir.add_subdir(dir);
+    }
+    term.write_str(&format!("Copied '{}' to '{}'\n", src, dst));
+}
+
+/// Duplicate a file's content under a new name
+fn copy_file(src: &File, name: &str) -> File {
+    let mut file = File::new(name);
+    file.overwrite(src.read());
+    file
+}
+
+/// Recursively duplicate a folder and everything below it
+fn copy_dir(src: &Directory, name: &'static str) -> Directory {
+    let mut dir = Directory::new(name);
+    for file in src.files.values() {
+        dir.add_file(copy_file(file, &file.name));
+    }
+    for sub in src.subdirs.values() {
+        dir.add_subdir(copy_dir(sub, sub.name));
+    }
+    dir
+}
+
+/// Line, word and byte counts of `content`, formatted like `wc`
+fn counts(content: &[u8]) -> String {
+    match core::str::from_utf8(content) {
+        Ok(text) => {
+            let lines = text.matches('\n').count();
+            let words = text.split_whitespace().count();
+            format!("{:>7} {:>7} {:>7}", lines, words, content.len())
+        }
+        // Binary content: only the byte count is meaningful
+        Err(_) => format!("{:>7} {:>7} {:>7}", "-", "-", content.len()),
+    }
+}
+
+/// Print line, word and byte counts of a file, like `wc`
+pub fn count_file(out: &mut dyn Output, dir: &Directory, name: &str) {
+    match dir.get_file(name) {
+        Some(file) => out.write_str(&format!("{} {}\n", counts(file.read()), name)),
+        None => out.write_str(&format!("'{}' not found\n", name)),
+    }
+}
+
+/// Print line, word and byte counts of piped text
+pub fn count_text(out: &mut dyn Output, text: &str) {
+    out.write_str(&format!("{}\n", counts(text.as_bytes())));
+}
+
+/// Sort lines lexicographically, optionally reversed and with duplicates dropped
+fn sorted_lines(text: &str, reverse: bool, unique: bool) -> Vec<&str> {
+    let mut lines: Vec<&str> = text.lines().collect();
+    lines.sort_unstable();
+    if reverse {
+        lines.reverse();
+    }
+    if unique {
+        lines.dedup();
+    }
+    lines
+}
+
+/// Print the lines of piped text in sorted order
+pub fn sort_text(out: &mut dyn Output, text: &str, reverse: bool, unique: bool) {
+    for line in sorted_lines(text, reverse, unique) {
+        out.write_str(line);
+        out.write_char('\n');
+    }
+}
+
+/// Print a file's lines in sorted order, or with `write` store them back in place
+pub fn sort_file(term: &mut Terminal, dir: &mut Directory, name: &str, reverse: bool, unique: bool, write: bool) {
+    let file = match dir.files.get_mut(name) {
+        Some(f) => f,
+        None => {
+            term.write_str(&format!("'{}' not found\n", name));
+            return;
+        }
+    };
+    let text = match core::str::from_utf8(file.read()) {
+        Ok(t) => t,
+        Err(_) => {
+            term.write_str(&format!("'{}' is not a text file\n", name));
+            return;
+        }
+    };
+
+    if !write {
+        let text = String::from(text);
+        sort_text(term, &text, reverse, unique);
+        return;
+    }
+    if file.locked {
+        term.write_str(&format!("'{}' is locked\n", name));
+        return;
+    }
+    let mut sorted = sorted_lines(text, reverse, unique).join("\n");
+    let count = sorted.lines().count();
+    if !sorted.is_empty() {
+        sorted.push('\n');
+    }
+    file.overwrite(sorted.as_bytes());
+    term.write_str(&format!("Sorted {} lines in '{}'\n", count, name));
+}
+
+/// Lines `head` and `tail` print when no count is given
+pub const HEAD_DEFAULT_LINES: usize = 10;
+
+/// Print the first `n` lines of `text`, or the last `n` with `tail`
+pub fn head_text(out: &mut dyn Output, text: &str, n: usize, tail: bool) {
+    let total = text.lines().count();
+    let skip = if tail { total.saturating_sub(n) } else { 0 };
+    for line in text.lines().skip(skip).take(n) {
+        out.write_str(line);
+        out.write_char('\n');
+    }
+}
+
+/// Print the first or last `n` lines of a text file
+pub fn head_file(out: &mut dyn Output, dir: &Directory, name: &str, n: usize, tail: bool) {
+    match dir.get_file(name).map(|f| core::str::from_utf8(f.read())) {
+        Some(Ok(text)) => head_text(out, text, n, tail),
+        Some(Err(_)) => out.write_str(&format!("'{}' is not a text file\n", name)),
+        None => out.write_str(&format!("'{}' not found\n", name)),
+    }
+}
+
+/// Print a detailed report on a single file or folder
+pub fn stat_entry(term: &mut Terminal, dir: &Directory, name: &str) {
+    if let Some(file) = dir.get_file(name) {
+        let size = file.read().len();
+        let (ch, cm, cs) = crate::sys::ticks_to_hms(file.created_ticks);
+        let (mh, mm, ms) = crate::sys::ticks_to_hms(file.modified_ticks);
+        term.write_str(&format!("Name:     {}\n", name));
+        term.write_str("Type:     file\n");
+        term.write_str(&format!("Size:     {} ({} bytes)\n", format_size(size), size));
+        term.write_str(&format!("Locked:   {}\n", if file.locked { "yes" } else { "no" }));
+        match core::str::from_utf8(file.read()) {
+            Ok(text) => {
+                term.write_str(&format!("Lines:    {}\n", text.lines().count()));
+                term.write_str("UTF-8:    valid\n");
+            }
+            Err(e) => term.write_str(&format!("UTF-8:    invalid at byte {}\n", e.valid_up_to())),
+        }
+        // Timestamps count from boot; there is no wall clock per file
+        term.write_str(&format!("Created:  {:02}:{:02}:{:02} uptime\n", ch, cm, cs));
+        term.write_str(&format!("Modified: {:02}:{:02}:{:02} uptime\n", mh, mm, ms));
+    } else if let Some(sub) = dir.subdirs.get(name) {
+        let (files, folders) = count_entries(sub);
+        term.write_str(&format!("Name:     {}\n", name));
+        term.write_str("Type:     folder\n");
+        term.write_str(&format!("Contains: {} files, {} folders\n", sub.files.len(), sub.subdirs.len()));
+        term.write_str(&format!("Below:    {} files, {} folders\n", files, folders));
+        term.write_str(&format!("Size:     {}\n", format_size(dir_size(sub))));
+    } else {
+        term.write_str(&format!("'{}' not found\n", name));
+    }
+}
+
+/// Files at or above this size are flagged by `du`
+const LARGE_FILE_BYTES: usize = 16 * 1024;
+
+/// Format a byte count with a B/KiB/MiB suffix
+pub fn format_size(bytes: usize) -> String {
+    if bytes < 1024 {
+        format!("{} B", bytes)
+    } else if bytes < 1024 * 1024 {
+        format!("{}.{} KiB", bytes / 1024, (bytes % 1024) * 10 / 1024)
+    } else {
+        format!("{}.{} MiB", bytes / (1024 * 1024), (bytes % (1024 * 1024)) * 10 / (1024 * 1024))
+    }
+}
+
+/// Is this a hidden entry, such as the trash?
+fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// Total size of all visible files in a folder and its subfolders
+pub fn dir_size(dir: &Directory) -> usize {
+    let files: usize = dir.files.values().filter(|f| !is_hidden(&f.name)).map(|f| f.content.len()).sum();
+    let subdirs: usize = dir.subdirs.values().filter(|d| !is_hidden(d.name)).map(|d| dir_size(d)).sum();
+    files + subdirs
+}
+
+/// Collect paths of files at or above `LARGE_FILE_BYTES`
+fn large_files(dir: &Directory, prefix: &str, out: &mut Vec<(String, usize)>) {
+    for file in dir.files.values().filter(|f| !is_hidden(&f.name)) {
+        if file.content.len() >= LARGE_FILE_BYTES {
+            out.push((format!("{}{}", prefix, file.name), file.content.len()));
+        }
+    }
+    for sub in dir.subdirs.values().filter(|d| !is_hidden(d.name)) {
+        large_files(sub, &format!("{}{}/", prefix, sub.name), out);
+    }
+}
+
+/// Report cumulative size per subfolder, largest first, plus a grand total
+pub fn disk_usage(term: &mut Terminal, cwd: &Directory, path: Option<&str>) {
+    let mut target = cwd;
+    for part in path.unwrap_or("").split('/').filter(|s| !s.is_empty()) {
+        if let Some(child) = target.subdirs.get(part) {
+            target = child;
+        } else {
+            term.write_str(&format!("Directory '{}' not found\n", part));
+            return;
+        }
+    }
+
+    let mut sizes: Vec<(&str, usize)> = target.subdirs.values()
+        .filter(|d| !is_hidden(d.name))
+        .map(|d| (d.name, dir_size(d)))
+        .collect();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1));
+
+    for (name, size) in sizes.iter() {
+        term.write_str(&format!("{:>10}  /{}\n", format_size(*size), name));
+    }
+
+    let own: usize = target.files.values().filter(|f| !is_hidden(&f.name)).map(|f| f.content.len()).sum();
+    term.write_str(&format!("{:>10}  (files here)\n", format_size(own)));
+
+    let mut large = Vec::new();
+    large_files(target, "", &mut large);
+    for (file, size) in large.iter() {
+        term.write_str(&format!("Large file: {} ({})\n", file, format_size(*size)));
+    }
+
+    term.write_str(&format!("{:>10}  total\n", format_size(dir_size(target))));
+}
+
+/// Collect paths below `dir` whose file or folder name contains `pattern`
+fn find_names(dir: &Directory, pattern: &str, prefix: &str, out: &mut Vec<String>) {
+    for sub in dir.subdirs.values().filter(|d| !is_hidden(d.name)) {
+        let path = format!("{}{}", prefix, sub.name);
+        if sub.name.contains(pattern) {
+            out.push(format!("{}/", path));
+        }
+        find_names(sub, pattern, &format!("{}/", path), out);
+    }
+    for file in dir.files.values().filter(|f| !is_hidden(&f.name)) {
+        if file.name.contains(pattern) {
+            out.push(format!("{}{}", prefix, file.name));
+        }
+    }
+}
+
+/// Recursively search the current folder for names containing `pattern`
+pub fn find_files(term: &mut Terminal, cwd: &Directory, pattern: &str) {
+    let mut found = Vec::new();
+    find_names(cwd, pattern, "", &mut found);
+
+    if found.is_empty() {
+        term.write_str(&format!("No matches for '{}'\n", pattern));
+        return;
+    }
+    for path in found.iter() {
+        term.write_str(path);
+        term.write_char('\n');
+    }
+}
+
+/// Parse a 1-based line range: `N` or `start-end`
+pub fn parse_line_range(spec: &str) -> Option<(usize, usize)> {
+    let (start, end) = match spec.split_once('-') {
+        Some((a, b)) => (a.trim().parse().ok()?, b.trim().parse().ok()?),
+        None => {
+            let n = spec.trim().parse().ok()?;
+            (n, n)
+        }
+    };
+    if start == 0 || end < start { None } else { Some((start, end)) }
+}
+
+/// Print a file, or only the given lines for `name:start-end` / `name:N`
+pub fn render_file(out: &mut dyn Output, dir: &Directory, spec: &str) {
+    let (name, range) = match spec.split_once(':') {
+        Some((name, range)) => (name, Some(range)),
+        None => (spec, None),
+    };
+
+    let file = match dir.get_file(name) {
+        Some(f) => f,
+        None => {
+            out.write_str(&format!("'{}' not found\n", name));
+            return;
+        }
+    };
+    let text = match core::str::from_utf8(file.read()) {
+        Ok(t) => t,
+        Err(_) => {
+            out.write_str("<binary, use peek -x to see it in hex>\n");
+            return;
+        }
+    };
+
+    let range = match range {
+        Some(r) => match parse_line_range(r) {
+            Some(r) => r,
+            None => {
+                out.write_str("Invalid line range. Use: peek file:start-end\n");
+                return;
+            }
+        },
+        None => {
+            out.write_str(text);
+            if !text.is_empty() && !text.ends_with('\n') {
+                out.write_char('\n');
+            }
+            return;
+        }
+    };
+
+    // Clamp the range to the file's line count
+    let (start, end) = range;
+    let count = text.lines().count();
+    if start > count {
+        out.write_str(&format!("'{}' has only {} lines\n", name, count));
+        return;
+    }
+    for (i, line) in text.lines().enumerate().take(end.min(count)).skip(start - 1) {
+        out.write_str(&format!("{:>4} | {}\n", i + 1, line));
+    }
+}
+
+/// Bytes shown per `peek -x` row
+const HEX_ROW_BYTES: usize = 16;
+
+/// Parse a byte offset, decimal or `0x`-prefixed hex
+fn parse_offset(s: &str) -> Option<usize> {
+    let s = s.trim();
+    match s.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+/// Hex and ASCII dump of a file, or only bytes `start..end` for `name:start-end`
+pub fn hexdump_file(out: &mut dyn Output, dir: &Directory, spec: &str) {
+    let (name, range) = match spec.split_once(':') {
+        Some((name, range)) => (name, Some(range)),
+        None => (spec, None),
+    };
+
+    let data = match dir.get_file(name) {
+        Some(f) => f.read(),
+        None => {
+            out.write_str(&format!("'{}' not found\n", name));
+            return;
+        }
+    };
+    if data.is_empty() {
+        out.write_str("(empty)\n");
+        return;
+    }
+
+    let (start, end) = match range {
+        None => (0, data.len()),
+        Some(r) => match r.split_once('-').and_then(|(a, b)| Some((parse_offset(a)?, parse_offset(b)?))) {
+            Some((start, end)) if start < end => (start, end.min(data.len())),
+            _ => {
+                out.write_str("Invalid byte range. Use: peek -x file:start-end\n");
+                return;
+            }
+        },
+    };
+    if start >= data.len() {
+        out.write_str(&format!("'{}' has only {} bytes\n", name, data.len()));
+        return;
+    }
+
+    for (row, chunk) in data[start..end].chunks(HEX_ROW_BYTES).enumerate() {
+        let mut line = format!("{:08x}  ", start + row * HEX_ROW_BYTES);
+        for i in 0..HEX_ROW_BYTES {
+            match chunk.get(i) {
+                Some(b) => line.push_str(&format!("{:02x} ", b)),
+                None => line.push_str("   "),
+            }
+            // Extra gap between the two 8-byte halves
+            if i == HEX_ROW_BYTES / 2 - 1 {
+                line.push(' ');
+            }
+        }
+        line.push_str(" |");
+        for &b in chunk {
+            line.push(if (0x20..0x7f).contains(&b) { b as char } else { '.' });
+        }
+        line.push_str("|\n");
+        out.write_str(&line);
+    }
+}
+
+/// Does `pat` match at the start of `text`? `.` matches any character and a
+/// trailing `$` requires the match to end where `text` ends.
+fn match_here(pat: &[char], text: &[char]) -> bool {
+    match pat.split_first() {
+        None => true,
+        Some(('$', [])) => text.is_empty(),
+        Some((p, rest)) => match text.split_first() {
+            Some((t, text_rest)) if *p == '.' || p == t => match_here(rest, text_rest),
+            _ => false,
+        },
+    }
+}
+
+/// Find the first match of a seek pattern in `line`, returning its char offset.
+///
+/// Patterns are literal except for `^` (start anchor), `$` (end anchor) and
+/// `.` (any character). `ignore_case` folds ASCII letters on both sides.
+pub fn pattern_match(pattern: &str, line: &str, ignore_case: bool) -> Option<usize> {
+    let fold = |c: char| if ignore_case { c.to_ascii_lowercase() } else { c };
+    let text: Vec<char> = line.chars().map(fold).collect();
+    let (anchored, pattern) = match pattern.strip_prefix('^') {
+        Some(rest) => (true, rest),
+        None => (false, pattern),
+    };
+    let pat: Vec<char> = pattern.chars().map(fold).collect();
+
+    if anchored {
+        return if match_here(&pat, &text) { Some(0) } else { None };
+    }
+    (0..=text.len()).find(|&i| match_here(&pat, &text[i..]))
+}
+
+/// Up to `SNIPPET_CHARS` characters of `line` around char offset `at`
+fn snippet(line: &str, at: usize) -> String {
+    const SNIPPET_CHARS: usize = 50;
+    let start = at.saturating_sub(SNIPPET_CHARS / 4);
+    let text: String = line.chars().skip(start).take(SNIPPET_CHARS).collect();
+    let text = text.trim();
+    if start > 0 { format!("...{}", text) } else { text.into() }
+}
+
+/// Most matches `seek` prints before summarizing the rest
+const SEEK_MAX_RESULTS: usize = 20;
+
+/// Collect `path:line: snippet` for the first matching line of each file,
+/// descending into subfolders when `recursive`
+fn seek_matches(dir: &Directory, pattern: &str, ignore_case: bool, recursive: bool, prefix: &str, out: &mut Vec<String>) {
+    // Hidden entries such as the trash are skipped, as in `peek`
+    for file in dir.files.values().filter(|f| !is_hidden(&f.name)) {
+        let text = match core::str::from_utf8(file.read()) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        for (i, line) in text.lines().enumerate() {
+            if let Some(at) = pattern_match(pattern, line, ignore_case) {
+                out.push(format!("{}{}:{}: {}", prefix, file.name, i + 1, snippet(line, at)));
+                break;
+            }
+        }
+    }
+    if recursive {
+        for sub in dir.subdirs.values().filter(|d| !is_hidden(d.name)) {
+            seek_matches(sub, pattern, ignore_case, true, &format!("{}{}/", prefix, sub.name), out);
+        }
+    }
+}
+
+/// Search the files of a folder (and with `recursive` its subfolders) for
+/// `pattern`, printing the first matching line of each file
+pub fn seek_in_cwd(out: &mut dyn Output, cwd: &Directory, pattern: &str, ignore_case: bool, recursive: bool) {
+    let mut matches = Vec::new();
+    seek_matches(cwd, pattern, ignore_case, recursive, "", &mut matches);
+
+    if matches.is_empty() {
+        out.write_str(&format!("No matches for '{}'\n", pattern));
+        return;
+    }
+    for m in matches.iter().take(SEEK_MAX_RESULTS) {
+        out.write_str(m);
+        out.write_char('\n');
+    }
+    if matches.len() > SEEK_MAX_RESULTS {
+        out.write_str(&format!("({} more)\n", matches.len() - SEEK_MAX_RESULTS));
+    }
+}
+
+/// Print every line of piped text that matches `pattern`, unchanged
+pub fn seek_in_text(out: &mut dyn Output, text: &str, pattern: &str, ignore_case: bool) {
+    for line in text.lines().filter(|line| pattern_match(pattern, line, ignore_case).is_some()) {
+        out.write_str(line);
+        out.write_char('\n');
+    }
+}
+
+/// Most cells the LCS table of `diff` may use; bigger changes are shown as
+/// all old lines removed, then all new lines added
+const DIFF_MAX_CELLS: usize = 8 * 1024;
+
+/// Line-based diff of `a` and `b` as `('-' | '+', line number, text)`.
+///
+/// Common leading and trailing lines are skipped before running a longest
+/// common subsequence on the rest.
+fn diff_lines<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<(char, usize, &'a str)> {
+    let prefix = a.iter().zip(b.iter()).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..].iter().rev().zip(b[prefix..].iter().rev()).take_while(|(x, y)| x == y).count();
+    let a_mid = &a[prefix..a.len() - suffix];
+    let b_mid = &b[prefix..b.len() - suffix];
+    let (n, m) = (a_mid.len(), b_mid.len());
+
+    let mut changes = Vec::new();
+    if (n + 1) * (m + 1) > DIFF_MAX_CELLS {
+        changes.extend(a_mid.iter().enumerate().map(|(i, line)| ('-', prefix + i + 1, *line)));
+        changes.extend(b_mid.iter().enumerate().map(|(j, line)| ('+', prefix + j + 1, *line)));
+        return changes;
+    }
+
+    // lcs[i * (m + 1) + j] = LCS length of a_mid[i..] and b_mid[j..]
+    let mut lcs = vec![0u16; (n + 1) * (m + 1)];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i * (m + 1) + j] = if a_mid[i] == b_mid[j] {
+                lcs[(i + 1) * (m + 1) + j + 1] + 1
+            } else {
+                lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && a_mid[i] == b_mid[j] {
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+            changes.push(('-', prefix + i + 1, a_mid[i]));
+            i += 1;
+        } else {
+            changes.push(('+', prefix + j + 1, b_mid[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+/// Compare two text files line by line, printing removed lines in red and
+/// added lines in green
+pub fn diff_files(term: &mut Terminal, dir: &Directory, a: &str, b: &str) {
+    let mut texts = [""; 2];
+    for (text, name) in texts.iter_mut().zip([a, b]) {
+        let file = match dir.get_file(name) {
+            Some(f) => f,
+            None => {
+                term.write_str(&format!("'{}' not found\n", name));
+                return;
+            }
+        };
+        *text = match core::str::from_utf8(file.read()) {
+            Ok(t) => t,
+            Err(_) => {
+                term.write_str(&format!("'{}' is not a text file\n", name));
+                return;
+            }
+        };
+    }
+
+    let a_lines: Vec<&str> = texts[0].lines().collect();
+    let b_lines: Vec<&str> = texts[1].lines().collect();
+    let changes = diff_lines(&a_lines, &b_lines);
+    if changes.is_empty() {
+        term.write_str("files are identical\n");
+        return;
+    }
+
+    let removed = ColorCode::new(Color::LightRed, Color::Black);
+    let added = ColorCode::new(Color::LightGreen, Color::Black);
+    term.write_colored_str(&format!("--- {}\n", a), removed);
+    term.write_colored_str(&format!("+++ {}\n", b), added);
+    for (sign, line_no, line) in changes {
+        let color = if sign == '-' { removed } else { added };
+        term.write_colored_str(&format!("{}{:>4} | {}\n", sign, line_no, line), color);
+    }
+}
+
+/// Files larger than this are reported by `fsck`
+const FSCK_MAX_FILE_BYTES: usize = 64 * 1024;
+
+/// Totals gathered by `fsck`
+#[derive(Default)]
+struct FsckStats {
+    dirs: usize,
+    files: usize,
+    issues: usize,
+    fixed: usize,
+}
+
+/// Check one folder and everything below it, reporting each problem
+fn fsck_dir(term: &mut Terminal, dir: &mut Directory, path: &str, fix: bool, stats: &mut FsckStats) {
+    stats.dirs += 1;
+
+    for (key, file) in dir.files.iter_mut() {
+        stats.files += 1;
+        if file.name != *key {
+            stats.issues += 1;
+            term.write_str(&format!("{}/{}: file is named '{}'\n", path, key, file.name));
+            if fix {
+                file.name = (*key).into();
+                stats.fixed += 1;
+            }
+        }
+        if !key.contains('.') {
+            stats.issues += 1;
+            term.write_str(&format!("{}/{}: file name has no '.'\n", path, key));
+        }
+        if file.read().len() > FSCK_MAX_FILE_BYTES {
+            stats.issues += 1;
+            term.write_str(&format!("{}/{}: {} exceeds {}\n", path, key, format_size(file.read().len()), format_size(FSCK_MAX_FILE_BYTES)));
+        }
+        if file.modified_ticks < file.created_ticks {
+            stats.issues += 1;
+            term.write_str(&format!("{}/{}: modified before it was created\n", path, key));
+            if fix {
+                file.modified_ticks = file.created_ticks;
+                stats.fixed += 1;
+            }
+        }
+    }
+
+    for (key, sub) in dir.subdirs.iter_mut() {
+        if sub.name != *key {
+            stats.issues += 1;
+            term.write_str(&format!("{}/{}: folder is named '{}'\n", path, key, sub.name));
+            if fix {
+                sub.name = key;
+                stats.fixed += 1;
+            }
+        }
+        // A leading '.' only marks the folder as hidden
+        if key.trim_start_matches('.').contains('.') {
+            stats.issues += 1;
+            term.write_str(&format!("{}/{}: folder name contains '.'\n", path, key));
+        }
+        fsck_dir(term, sub, &format!("{}/{}", path, key), fix, stats);
+    }
+}
+
+/// Walk the whole tree verifying its invariants; with `fix`, repair the
+/// problems that have an unambiguous fix (names are reset to their keys)
+pub fn fsck(term: &mut Terminal, root: &mut Directory, fix: bool) {
+    let mut stats = FsckStats::default();
+    let path = root.name;
+    fsck_dir(term, root, path, fix, &mut stats);
+    term.write_str(&format!(
+        "checked {} dirs, {} files, {} issues",
+        stats.dirs, stats.files, stats.issues
+    ));
+    if fix {
+        term.write_str(&format!(", {} fixed", stats.fixed));
+    }
+    term.write_char('\n');
+}
+
+/// First line of every `export` bundle
+const BUNDLE_HEADER: &[u8] = b"katalyst-bundle 1\n";
+
+/// Append `dir`'s contents to a bundle.
+///
+/// Each folder is a `D path` line. Each file is an `F path size` line,
+/// followed by exactly `size` bytes of content and a newline.
+fn bundle_dir(dir: &Directory, prefix: &str, out: &mut Vec<u8>) {
+    for file in dir.files.values() {
+        out.extend_from_slice(format!("F {}{} {}\n", prefix, file.name, file.read().len()).as_bytes());
+        out.extend_from_slice(file.read());
+        out.push(b'\n');
+    }
+    for sub in dir.subdirs.values() {
+        let path = format!("{}{}", prefix, sub.name);
+        out.extend_from_slice(format!("D {}\n", path).as_bytes());
+        bundle_dir(sub, &format!("{}/", path), out);
+    }
+}
+
+/// Can `part` be used as one component of a path inside a bundle?
+fn bundle_component_ok(part: &str) -> bool {
+    !part.is_empty() && part != "." && part != ".."
+}
+
+/// Can `part` name a folder? Like `fsck`, only a leading '.' is allowed.
+fn bundle_folder_ok(part: &str) -> bool {
+    bundle_component_ok(part) && !part.trim_start_matches('.').contains('.')
+}
+
+/// Rebuild a folder called `name` from a bundle made by `bundle_dir`
+///
+/// The bundle is untrusted: sizes are range-checked and path components
+/// must be real names, so a crafted bundle can't break navigation.
+fn unbundle(data: &[u8], name: &'static str) -> Result<Directory, &'static str> {
+    let data = data.strip_prefix(BUNDLE_HEADER).ok_or("not an export bundle")?;
+    let mut root = Directory::new(name);
+    let mut created = 0;
+    let mut pos = 0;
+    while pos < data.len() {
+        let end = pos + data[pos..].iter().position(|&b| b == b'\n').ok_or("truncated bundle")?;
+        let line = core::str::from_utf8(&data[pos..end]).map_err(|_| "corrupt bundle")?;
+        pos = end + 1;
+
+        match line.split_once(' ') {
+            Some(("D", path)) => {
+                let parts: Vec<&str> = path.split('/').collect();
+                if !parts.iter().all(|p| bundle_folder_ok(p)) {
+                    return Err("corrupt bundle");
+                }
+                ensure_path(&mut root, &parts, &mut created);
+            }
+            Some(("F", spec)) => {
+                let (path, size) = spec.rsplit_once(' ').ok_or("corrupt bundle")?;
+                let size: usize = size.parse().map_err(|_| "corrupt bundle")?;
+                let content_end = pos.checked_add(size).ok_or("corrupt bundle")?;
+                let content = data.get(pos..content_end).ok_or("truncated bundle")?;
+                pos = content_end + 1;
+
+                let mut parts: Vec<&str> = path.split('/').collect();
+                let file_name = parts.pop().unwrap_or("");
+                let folders_ok = parts.iter().all(|p| bundle_folder_ok(p));
+                if !folders_ok || !bundle_component_ok(file_name) || !file_name.contains('.') {
+                    return Err("corrupt bundle");
+                }
+                let mut file = File::new(file_name);
+                file.overwrite(content);
+                ensure_path(&mut root, &parts, &mut created).add_file(file);
+            }
+            _ => return Err("corrupt bundle"),
+        }
+    }
+    Ok(root)
+}
+
+/// Write folder `name` and everything below it into the single file `out_name`
+pub fn export_dir(term: &mut Terminal, cwd: &mut Directory, name: &str, out_name: &str) {
+    let dir = match cwd.subdirs.get(name) {
+        Some(d) => d,
+        None => {
+            term.write_str(&format!("Folder '{}' not found\n", name));
+            return;
+        }
+    };
+    if !out_name.contains('.') {
+        term.write_str("File names must contain a '.'\n");
+        return;
+    }
+    if cwd.get_file(out_name).is_some_and(|f| f.locked) {
+        term.write_str(&format!("'{}' is locked\n", out_name));
+        return;
+    }
+
+    let mut bundle = BUNDLE_HEADER.to_vec();
+    bundle_dir(dir, "", &mut bundle);
+    let (files, dirs) = count_entries(dir);
+    if cwd.get_file(out_name).is_none() {
+        cwd.add_file(File::new(out_name));
+    }
+    cwd.files.get_mut(out_name).unwrap().overwrite(&bundle);
+    term.write_str(&format!(
+        "Exported '{}' ({} files, {} folders, {}) to '{}'\n",
+        name, files, dirs, format_size(bundle.len()), out_name
+    ));
+}
+
+/// Recreate the folder saved in bundle file `in_name` as a new folder `name`
+pub fn import_dir(term: &mut Terminal, cwd: &mut Directory, in_name: &str, name: &str) {
+    if name.contains('.') {
+        term.write_str("Folder names cannot contain a '.'\n");
+        return;
+    }
+    if cwd.subdirs.contains_key(name) {
+        term.write_str(&format!("'{}' already exists\n", name));
+        return;
+    }
+    let data = match cwd.get_file(in_name) {
+        Some(f) => f.read(),
+        None => {
+            term.write_str(&format!("'{}' not found\n", in_name));
+            return;
+        }
+    };
+
+    match unbundle(data, intern(name)) {
+        Ok(dir) => {
+            let (files, dirs) = count_entries(&dir);
+            cwd.add_subdir(dir);
+            term.write_str(&format!("Imported {} files, {} folders into '{}'\n", files, dirs, name));
+        }
+        Err(msg) => term.write_str(&format!("Cannot import '{}': {}\n", in_name, msg)),
+    }
+}
+
+/// Hidden folder in root that keeps deleted entries until `empty-trash`
+pub const TRASH_DIR: &str = ".trash";
+
+/// Numbered trash slots, oldest first
+fn trash_slots(trash: &Directory) -> Vec<(usize, &Directory)> {
+    let mut slots: Vec<(usize, &Directory)> = trash
+        .subdirs
+        .iter()
+        .filter_map(|(key, slot)| Some((key.parse().ok()?, &**slot)))
+        .collect();
+    slots.sort_by_key(|(n, _)| *n);
+    slots
+}
+
+/// Put a deleted entry in a new numbered slot of the trash
+pub fn trash_entry(root: &mut Directory, entry: Entry) {
+    if !root.subdirs.contains_key(TRASH_DIR) {
+        root.add_subdir(Directory::new(intern(TRASH_DIR)));
+    }
+    let trash = root.get_subdir_mut(TRASH_DIR).unwrap();
+    let next = trash_slots(trash).last().map_or(1, |(n, _)| n + 1);
+    let mut slot = Directory::new(intern(&format!("{}", next)));
+    match entry {
+        Entry::File(file) => slot.add_file(file),
+        Entry::Dir(dir) => slot.add_subdir(dir),
+    }
+    trash.add_subdir(slot);
+}
+
+/// Take the most recently deleted entry called `name` out of the trash
+pub fn take_from_trash(root: &mut Directory, name: &str) -> Option<Entry> {
+    let trash = root.get_subdir_mut(TRASH_DIR)?;
+    let slot = trash_slots(trash)
+        .into_iter()
+        .rev()
+        .find(|(_, slot)| slot.files.contains_key(name) || slot.subdirs.contains_key(name))
+        .map(|(_, slot)| slot.name)?;
+
+    let mut holder = trash.remove_subdir(slot)?;
+    let entry = match holder.remove_file(name) {
+        Some(file) => Entry::File(file),
+        None => Entry::Dir(holder.remove_subdir(name)?),
+    };
+    Some(entry)
+}
+
+/// Put a restored entry back into `dir`, unless the name is taken
+pub fn restore_entry(term: &mut Terminal, dir: &mut Directory, entry: Entry) -> Result<(), Entry> {
+    let name = match &entry {
+        Entry::File(file) => file.name.clone(),
+        Entry::Dir(sub) => sub.name.into(),
+    };
+    if dir.files.contains_key(name.as_str()) || dir.subdirs.contains_key(name.as_str()) {
+        term.write_str(&format!("'{}' already exists here\n", name));
+        return Err(entry);
+    }
+    match entry {
+        Entry::File(file) => dir.add_file(file),
+        Entry::Dir(sub) => dir.add_subdir(sub),
+    }
+    term.write_str(&format!("Restored '{}'\n", name));
+    Ok(())
+}
+
+/// List the trash, oldest deletion first
+pub fn list_trash(term: &mut Terminal, root: &Directory) {
+    let slots = root.subdirs.get(TRASH_DIR).map(|t| trash_slots(t)).unwrap_or_default();
+    if slots.is_empty() {
+        term.write_str("Trash is empty\n");
+    }
+    for (n, slot) in slots {
+        for file in slot.files.values() {
+            term.write_str(&format!("{:>4}  {}  ({})\n", n, file.name, format_size(file.read().len())));
+        }
+        for sub in slot.subdirs.values() {
+            term.write_str(&format!("{:>4}  {}/  ({})\n", n, sub.name, format_size(dir_size(sub))));
+        }
+    }
+}
+
+/// Permanently delete everything in the trash
+pub fn empty_trash(term: &mut Terminal, root: &mut Directory) {
+    match root.remove_subdir(TRASH_DIR) {
+        Some(trash) => {
+            let count = trash.subdirs.len();
+            term.write_str(&format!("Permanently deleted {} item(s)\n", count));
+        }
+        None => term.write_str("Trash is empty\n"),
+    }
+}
+
+/// A text file with the given content, for tests
+#[cfg(test)]
+fn text_file(name: &str, text: &str) -> File {
+    let mut file = File::new(name);
+    file.overwrite(text.as_bytes());
+    file
+}
+
+#[test_case]
+fn test_pattern_match() {
+    assert_eq!(pattern_match("lo", "hello", false), Some(3));
+    assert_eq!(pattern_match("^he", "hello", false), Some(0));
+    assert_eq!(pattern_match("^lo", "hello", false), None);
+    assert_eq!(pattern_match("lo$", "hello", false), Some(3));
+    assert_eq!(pattern_match("he$", "hello", false), None);
+    assert_eq!(pattern_match("h.l", "hello", false), Some(0));
+    assert_eq!(pattern_match("HEL", "hello", false), None);
+    assert_eq!(pattern_match("HEL", "hello", true), Some(0));
+    assert_eq!(pattern_match("", "hello", false), Some(0));
+}
+
+#[test_case]
+fn test_seek() {
+    let mut text = String::new();
+    seek_in_text(&mut text, "apple\nbanana\ncherry\n", "an", false);
+    assert_eq!(text, "banana\n");
+
+    let mut dir = Directory::new("main");
+    dir.add_file(text_file("notes.txt", "first\nneedle here\n"));
+    let mut trash = Directory::new(TRASH_DIR);
+    trash.add_file(text_file("old.txt", "needle\n"));
+    dir.add_subdir(trash);
+
+    let mut out = String::new();
+    seek_in_cwd(&mut out, &dir, "needle", false, true);
+    assert_eq!(out, "notes.txt:2: needle here\n");
+
+    let mut out = String::new();
+    seek_in_cwd(&mut out, &dir, "missing", false, true);
+    assert_eq!(out, "No matches for 'missing'\n");
+}
+
+#[test_case]
+fn test_diff_lines() {
+    assert!(diff_lines(&["a", "b"], &["a", "b"]).is_empty());
+    assert_eq!(diff_lines(&["a", "b", "c"], &["a", "x", "c"]), vec![('-', 2, "b"), ('+', 2, "x")]);
+    assert_eq!(diff_lines(&["a", "c"], &["a", "b", "c"]), vec![('+', 2, "b")]);
+}
+
+#[test_case]
+fn test_diff_lines_cutoff() {
+    // 100 x 100 differing lines need more than DIFF_MAX_CELLS, so every old
+    // line is removed and every new one added
+    let old: Vec<String> = (0..100).map(|i| format!("old {}", i)).collect();
+    let new: Vec<String> = (0..100).map(|i| format!("new {}", i)).collect();
+    let a: Vec<&str> = old.iter().map(|s| s.as_str()).collect();
+    let b: Vec<&str> = new.iter().map(|s| s.as_str()).collect();
+    assert!(101 * 101 > DIFF_MAX_CELLS);
+
+    let changes = diff_lines(&a, &b);
+    assert_eq!(changes.len(), 200);
+    assert_eq!(changes[0], ('-', 1, "old 0"));
+    assert_eq!(changes[99], ('-', 100, "old 99"));
+    assert_eq!(changes[100], ('+', 1, "new 0"));
+    assert_eq!(changes[199], ('+', 100, "new 99"));
+}
+
+#[test_case]
+fn test_unbundle_round_trip() {
+    let mut dir = Directory::new("src");
+    dir.add_file(text_file("a.txt", "hello\n"));
+    let mut sub = Directory::new("docs");
+    sub.add_file(text_file("b.md", "bye"));
+    dir.add_subdir(sub);
+
+    let mut bundle = BUNDLE_HEADER.to_vec();
+    bundle_dir(&dir, "", &mut bundle);
+    let copy = unbundle(&bundle, "copy").unwrap();
+    assert_eq!(copy.get_file("a.txt").unwrap().read(), b"hello\n");
+    assert_eq!(copy.subdirs.get("docs").unwrap().get_file("b.md").unwrap().read(), b"bye");
+}
+
+#[test_case]
+fn test_unbundle_malformed() {
+    let bundle = |body: &str| {
+        let mut data = BUNDLE_HEADER.to_vec();
+        data.extend_from_slice(body.as_bytes());
+        data
+    };
+    assert_eq!(unbundle(b"F a.txt 0\n\n", "x").err(), Some("not an export bundle"));
+    assert_eq!(unbundle(&bundle("X a.txt\n"), "x").err(), Some("corrupt bundle"));
+    assert_eq!(unbundle(&bundle("D docs"), "x").err(), Some("truncated bundle"));
+    assert_eq!(unbundle(&bundle("F a.txt\n"), "x").err(), Some("corrupt bundle"));
+    assert_eq!(unbundle(&bundle("D ../up\n"), "x").err(), Some("corrupt bundle"));
+    assert_eq!(unbundle(&bundle("D a//b\n"), "x").err(), Some("corrupt bundle"));
+    assert_eq!(unbundle(&bundle("D a.b\n"), "x").err(), Some("corrupt bundle"));
+    assert_eq!(unbundle(&bundle("F noext 0\n\n"), "x").err(), Some("corrupt bundle"));
+    assert_eq!(unbundle(&bundle("F ../a.txt 0\n\n"), "x").err(), Some("corrupt bundle"));
+}
+
+#[test_case]
+fn test_unbundle_oversized() {
+    let bundle = |body: &str| {
+        let mut data = BUNDLE_HEADER.to_vec();
+        data.extend_from_slice(body.as_bytes());
+        data
+    };
+    // Sizes past the end of the data, past usize::MAX, or overflowing the offset
+    assert_eq!(unbundle(&bundle("F a.txt 999\nhi\n"), "x").err(), Some("truncated bundle"));
+    assert_eq!(unbundle(&bundle("F a.txt 99999999999999999999999\n"), "x").err(), Some("corrupt bundle"));
+    assert_eq!(unbundle(&bundle(&format!("F a.txt {}\n", usize::MAX)), "x").err(), Some("corrupt bundle"));
+}
+
+#[test_case]
+fn test_parse_line_range() {
+    assert_eq!(parse_line_range("3"), Some((3, 3)));
+    assert_eq!(parse_line_range("2-5"), Some((2, 5)));
+    assert_eq!(parse_line_range(" 2 - 5 "), Some((2, 5)));
+    assert_eq!(parse_line_range("0"), None);
+    assert_eq!(parse_line_range("5-2"), None);
+    assert_eq!(parse_line_range("2-"), None);
+    assert_eq!(parse_line_range("a-b"), None);
+}
+
+#[test_case]
+fn test_parse_offset() {
+    assert_eq!(parse_offset("16"), Some(16));
+    assert_eq!(parse_offset("0x10"), Some(16));
+    assert_eq!(parse_offset(" 0xff "), Some(255));
+    assert_eq!(parse_offset("0xzz"), None);
+    assert_eq!(parse_offset("-1"), None);
+}
+
+#[test_case]
+fn test_sorted_lines() {
+    let text = "b\na\nb\n";
+    assert_eq!(sorted_lines(text, false, false), ["a", "b", "b"]);
+    assert_eq!(sorted_lines(text, true, false), ["b", "b", "a"]);
+    assert_eq!(sorted_lines(text, false, true), ["a", "b"]);
+    assert_eq!(sorted_lines(text, true, true), ["b", "a"]);
+    assert!(sorted_lines("", false, false).is_empty());
+}
+
+#[test_case]
+fn test_head_text() {
+    let head = |n, tail| {
+        let mut out = String::new();
+        head_text(&mut out, "1\n2\n3\n", n, tail);
+        out
+    };
+    assert_eq!(head(2, false), "1\n2\n");
+    assert_eq!(head(2, true), "2\n3\n");
+    assert_eq!(head(10, false), "1\n2\n3\n");
+    assert_eq!(head(10, true), "1\n2\n3\n");
+    assert_eq!(head(0, true), "");
+}
+
+#[test_case]
+fn test_ensure_path_reuses_existing() {
+    let mut root = Directory::new("main");
+    let mut a = Directory::new("a");
+    a.add_file(text_file("keep.txt", "x"));
+    root.add_subdir(a);
+
+    let mut created = 0;
+    ensure_path(&mut root, &["a", "b"], &mut created);
+    assert_eq!(created, 1);
+    let a = root.subdirs.get("a").unwrap();
+    assert!(a.get_file("keep.txt").is_some());
+    assert!(a.subdirs.contains_key("b"));
+
+    let mut created = 0;
+    ensure_path(&mut root, &["a", "b"], &mut created);
+    assert_eq!(created, 0);
+}
+
+#[test_case]
+fn test_make_dirs() {
+    let mut term = Terminal::new("");
+    let mut root = Directory::new("main");
+    make_dirs(&mut term, &mut root, "a/b");
+    make_dirs(&mut term, &mut root, "/a/b/c/");
+    let b = root.subdirs.get("a").unwrap().subdirs.get("b").unwrap();
+    assert!(b.subdirs.contains_key("c"));
+    assert_eq!(root.subdirs.len(), 1);
+
+    make_dirs(&mut term, &mut root, "x.y/z");
+    assert!(!root.subdirs.contains_key("x.y"));
+}
@@ -1,10 +1,29 @@
 use super::file::File;
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, BTreeSet};
 use alloc::boxed::Box;
 use alloc::{vec::Vec, string::String};
+use spin::Mutex;
+use lazy_static::lazy_static;
 
 use crate::alloc::string::ToString;
 
+lazy_static! {
+    /// Every name ever leaked for use as a `&'static str` key
+    static ref NAMES: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+}
+
+/// Return a `&'static str` for `name`, leaking a new one only the first time
+/// a name is seen, so re-adding or renaming entries doesn't leak repeatedly.
+pub fn intern(name: &str) -> &'static str {
+    let mut names = NAMES.lock();
+    if let Some(existing) = names.get(name) {
+        return existing;
+    }
+    let leaked: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.insert(leaked);
+    leaked
+}
+
 pub struct Directory {
     pub name: &'static str,
     pub files: BTreeMap<&'static str, File>,
@@ -32,16 +51,46 @@ impl Directory {
 
     // Add a file
     pub fn add_file(&mut self, file: File) {
-        let key = Box::leak(file.name.clone().into_boxed_str());
+        let key = intern(&file.name);
         self.files.insert(key, file);
     }
 
     // Add a subdirectory
     pub fn add_subdir(&mut self, dir: Directory) {
-        let key = Box::leak(dir.name.to_string().into_boxed_str());
+        let key = intern(dir.name);
         self.subdirs.insert(key, Box::new(dir));
     }
 
+    // Rename a file, keeping its key and name in sync; false if missing or taken
+    pub fn rename_file(&mut self, from: &str, to: &str) -> bool {
+        if self.files.contains_key(to) {
+            return false;
+        }
+        match self.files.remove(from) {
+            Some(mut file) => {
+                file.name = to.to_string();
+                self.add_file(file);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Rename a subdirectory, keeping its key and name in sync; false if missing or taken
+    pub fn rename_subdir(&mut self, from: &str, to: &str) -> bool {
+        if self.subdirs.contains_key(to) {
+            return false;
+        }
+        match self.remove_subdir(from) {
+            Some(mut dir) => {
+                dir.name = intern(to);
+                self.add_subdir(dir);
+                true
+            }
+            None => false,
+        }
+    }
+
     // Get a file by name
     pub fn get_file(&self, name: &str) -> Option<&File> {
         self.files.get(name)
@@ -62,3 +111,43 @@ impl Directory {
         self.subdirs.iter().map(|(_, d)| d.name.to_string()).collect()
     }
 }
+
+#[test_case]
+fn test_intern() {
+    let name = String::from("interned");
+    let first = intern(&name);
+    let second = intern("interned");
+    assert_eq!(first, "interned");
+    assert!(core::ptr::eq(first, second));
+    assert!(!core::ptr::eq(first, intern("other")));
+}
+
+#[test_case]
+fn test_rename_file() {
+    let mut dir = Directory::new("main");
+    dir.add_file(File::new("a.txt"));
+    dir.add_file(File::new("b.txt"));
+
+    assert!(!dir.rename_file("a.txt", "b.txt"));
+    assert!(!dir.rename_file("missing.txt", "c.txt"));
+    assert!(dir.get_file("a.txt").is_some());
+
+    assert!(dir.rename_file("a.txt", "c.txt"));
+    assert!(dir.get_file("a.txt").is_none());
+    assert_eq!(dir.get_file("c.txt").unwrap().name, "c.txt");
+}
+
+#[test_case]
+fn test_rename_subdir() {
+    let mut dir = Directory::new("main");
+    dir.add_subdir(Directory::new("a"));
+    dir.add_subdir(Directory::new("b"));
+
+    assert!(!dir.rename_subdir("a", "b"));
+    assert!(!dir.rename_subdir("missing", "c"));
+    assert!(dir.subdirs.contains_key("a"));
+
+    assert!(dir.rename_subdir("a", "c"));
+    assert!(!dir.subdirs.contains_key("a"));
+    assert_eq!(dir.subdirs.get("c").unwrap().name, "c");
+}
@@ -1,24 +1,47 @@
 use alloc::string::String;
 use alloc::vec::Vec;
+use core::sync::atomic::Ordering;
+
+use crate::sys::UPTIME_TICKS;
 
 pub struct File {
     pub name: String,
     pub content: Vec<u8>, // stored in memory; flush to disk for persistence
+    pub created_ticks: u64,  // UPTIME_TICKS when the file was created
+    pub modified_ticks: u64, // UPTIME_TICKS of the last write
+    pub locked: bool,        // commands refuse to change or delete the file while set
 }
 
 impl File {
     pub fn new(name: &str) -> Self {
+        let now = UPTIME_TICKS.load(Ordering::Relaxed);
         Self {
             name: name.into(),
             content: Vec::new(),
+            created_ticks: now,
+            modified_ticks: now,
+            locked: false,
         }
     }
 
-    pub fn write(&mut self, data: &[u8]) {
+    /// Add `data` after the existing content
+    pub fn append(&mut self, data: &[u8]) {
         self.content.extend_from_slice(data);
+        self.touch();
+    }
+
+    /// Replace the whole content with `data`
+    pub fn overwrite(&mut self, data: &[u8]) {
+        self.content.clear();
+        self.append(data);
     }
 
     pub fn read(&self) -> &[u8] {
         &self.content
     }
+
+    /// Update the modification time to now
+    pub fn touch(&mut self) {
+        self.modified_ticks = UPTIME_TICKS.load(Ordering::Relaxed);
+    }
 }
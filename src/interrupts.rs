@@ -75,7 +75,8 @@ use crate::sys::UPTIME_TICKS;
 
 extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
     // Increment tick counter
-    UPTIME_TICKS.fetch_add(1, core::sync::atomic::Ordering::Relaxed);
+    let now = UPTIME_TICKS.fetch_add(1, core::sync::atomic::Ordering::Relaxed) + 1;
+    crate::task::timer::on_tick(now);
 
     // End of interrupt
     unsafe {
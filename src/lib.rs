@@ -18,6 +18,8 @@ pub mod vga_buffer;
 pub mod repl;  // <- add repl module
 pub mod sys;
 pub mod fs;
+pub mod rtc;
+pub mod clipboard;
 
 
 use crate::sys::IDLE_TICKS;
@@ -27,6 +29,7 @@ pub fn init() {
     gdt::init();
     interrupts::init_idt();
     unsafe { interrupts::PICS.lock().initialize() };
+    sys::init_timer();
     x86_64::instructions::interrupts::enable();
 }
 pub trait Testable {
@@ -90,8 +93,16 @@ entry_point!(test_kernel_main);
 
 /// Entry point for `cargo xtest`
 #[cfg(test)]
-fn test_kernel_main(_boot_info: &'static BootInfo) -> ! {
+fn test_kernel_main(boot_info: &'static BootInfo) -> ! {
+    use memory::BootInfoFrameAllocator;
+    use x86_64::VirtAddr;
+
     init();
+    // Unit tests for the shell and file system allocate, so they need the heap
+    let phys_mem_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(phys_mem_offset) };
+    let mut frame_allocator = unsafe { BootInfoFrameAllocator::init(&boot_info.memory_map) };
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("heap initialization failed");
     test_main();
     hlt_loop();
 }
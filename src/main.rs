@@ -3,8 +3,7 @@
 
 extern crate alloc;
 
-use blog_os::println;
-use blog_os::task::{Task, executor::Executor};
+use blog_os::task::{Task, executor::{self, EXECUTOR}};
 use blog_os::repl::katalyst_repl;
 use bootloader::{BootInfo, entry_point};
 use core::panic::PanicInfo;
@@ -24,13 +23,11 @@ fn kernel_main(boot_info: &'static BootInfo) -> ! {
     allocator::init_heap(&mut mapper, &mut frame_allocator)
         .expect("heap initialization failed");
 
-    let mut executor = Executor::new();
-    executor.spawn(Task::new(katalyst_repl()));
-    executor.run();
+    EXECUTOR.lock().spawn(Task::new(katalyst_repl()));
+    executor::run_global();
 }
 
 #[panic_handler]
 fn panic(info: &PanicInfo) -> ! {
-    println!("{}", info);
-    blog_os::hlt_loop();
+    blog_os::sys::panic_screen(info)
 }
@@ -1,4 +1,6 @@
 use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use conquer_once::spin::OnceCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use x86_64::{
     PhysAddr, VirtAddr,
     structures::paging::{FrameAllocator, OffsetPageTable, PageTable, PhysFrame, Size4KiB},
@@ -44,6 +46,30 @@ unsafe impl FrameAllocator<Size4KiB> for EmptyFrameAllocator {
     }
 }
 
+/// The bootloader's memory map, recorded by `BootInfoFrameAllocator::init`
+static MEMORY_MAP: OnceCell<&'static MemoryMap> = OnceCell::uninit();
+
+/// Frames handed out by the `BootInfoFrameAllocator` so far
+static FRAMES_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+/// The bootloader's memory map, once the frame allocator has been created
+pub fn memory_map() -> Option<&'static MemoryMap> {
+    MEMORY_MAP.get().copied()
+}
+
+/// Frames allocated so far and the total number of usable frames
+pub fn frame_stats() -> (usize, usize) {
+    let usable = memory_map()
+        .map(|map| {
+            map.iter()
+                .filter(|r| r.region_type == MemoryRegionType::Usable)
+                .map(|r| ((r.range.end_addr() - r.range.start_addr()) / 4096) as usize)
+                .sum()
+        })
+        .unwrap_or(0);
+    (FRAMES_ALLOCATED.load(Ordering::Relaxed), usable)
+}
+
 /// A FrameAllocator that returns usable frames from the bootloader's memory map.
 pub struct BootInfoFrameAllocator {
     memory_map: &'static MemoryMap,
@@ -57,6 +83,7 @@ impl BootInfoFrameAllocator {
     /// memory map is valid. The main requirement is that all frames that are marked
     /// as `USABLE` in it are really unused.
     pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
+        let _ = MEMORY_MAP.try_init_once(|| memory_map);
         BootInfoFrameAllocator {
             memory_map,
             next: 0,
@@ -81,6 +108,9 @@ unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
     fn allocate_frame(&mut self) -> Option<PhysFrame> {
         let frame = self.usable_frames().nth(self.next);
         self.next += 1;
+        if frame.is_some() {
+            FRAMES_ALLOCATED.store(self.next, Ordering::Relaxed);
+        }
         frame
     }
 }
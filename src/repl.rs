@@ -1,16 +1,19 @@
 extern crate alloc;
 
-use alloc::{string::String, vec::Vec};
+use alloc::{collections::{BTreeMap, VecDeque}, string::String, vec::Vec};
 use core::fmt::Write;
 
 use crate::task::keyboard::ScancodeStream;
-use pc_keyboard::{DecodedKey, Keyboard, ScancodeSet1, layouts, HandleControl};
-use futures_util::stream::StreamExt;
+use crate::task::executor::EXECUTOR;
+use crate::task::timer::{sleep_ms, sleep_until};
+use crate::task::{Task, TaskId};
+use crate::vga_buffer::{Color, ColorCode, to_cp437};
+use pc_keyboard::{DecodedKey, KeyCode, KeyState, Keyboard, ScancodeSet1, layouts::{self, AnyLayout}, HandleControl};
+use futures_util::{future::{select, Either}, stream::StreamExt};
 
-use crate::fs::commands::{spawn_file_folder, despawn_file_folder, scan_files};
+use crate::fs::commands::{spawn_file_folder, despawn_file_folder, scan_files, move_file_folder, copy_file_folder, count_file, disk_usage, find_files, render_file, seek_in_cwd, count_text, seek_in_text, touch_file, stat_entry, hexdump_file, diff_files, set_locked, fsck, make_dirs, export_dir, import_dir, trash_entry, take_from_trash, restore_entry, list_trash, empty_trash, sort_file, sort_text, head_file, head_text, HEAD_DEFAULT_LINES};
 use crate::fs::storage::ROOT_DIR;
 use crate::fs::dir::Directory;
-use crate::fs::file::File;
 
 use crate::alloc::string::ToString;
 
@@ -21,31 +24,185 @@ const VGA_BUFFER: *mut u8 = 0xb8000 as *mut u8;
 const WIDTH: usize = 80;
 const HEIGHT: usize = 25;
 
+/// Maximum number of remembered commands
+const HISTORY_LIMIT: usize = 500;
+/// Entries listed by a plain `history`
+const HISTORY_SHOWN: usize = 20;
+
+/// Variables computed from the shell state; they can't be set or unset
+const BUILTIN_VARS: &[&str] = &["CWD", "UPTIME"];
+
+/// Keyboard layouts selectable with `layout`
+const LAYOUTS: &[&str] = &["us", "uk", "de", "azerty", "colemak", "dvorak", "dvp", "jis"];
+
+/// Prompt template used until one is set with `prompt`
+const DEFAULT_PROMPT: &str = "katalyst@%p=> ";
+
+/// Background tasks that `spawn` can start
+const BACKGROUND_TASKS: &[&str] = &["clock"];
+
+/// Maximum number of chained alias expansions before giving up
+const ALIAS_DEPTH_LIMIT: usize = 8;
+
+/// Shown at the bottom of the screen while paging output
+const PAGER_PROMPT: &str = "-- more -- (space: page, enter: line, q: quit)";
+
+/// Number of lines kept after they scroll off the top (bounded by the small heap)
+const SCROLLBACK_LINES: usize = 100;
+
+/// One screen row of VGA character/attribute byte pairs
+type Row = [u8; WIDTH * 2];
+
+/// A set of Terminal colors
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub name: &'static str,
+    pub text: ColorCode,
+    pub prompt: ColorCode,
+}
+
+/// Selectable themes; the first one is the default
+const THEMES: &[Theme] = &[
+    Theme {
+        name: "classic",
+        text: ColorCode::new(Color::White, Color::Black),
+        prompt: ColorCode::new(Color::LightCyan, Color::Black),
+    },
+    Theme {
+        name: "amber",
+        text: ColorCode::new(Color::Yellow, Color::Black),
+        prompt: ColorCode::new(Color::Brown, Color::Black),
+    },
+    Theme {
+        name: "green-phosphor",
+        text: ColorCode::new(Color::LightGreen, Color::Black),
+        prompt: ColorCode::new(Color::Green, Color::Black),
+    },
+];
+
+/// Command names offered by tab completion
+const COMMANDS: &[&str] = &[
+    "help", "wipe", "wp", "halt", "shutdown", "reboot", "spark", "core", "here", "date", "kill", "theme", "monitor",
+    "make", "del", "mv", "cp", "peek", "->", "<-", "wc", "du", "find", "seek", "alias", "unalias", "back",
+    "man", "set", "unset", "env", "touch", "layout", "bind", "unbind", "stat", "diff", "lock", "unlock", "spawn", "jobs", "history", "fsck", "export", "import", "undelete", "trash", "empty-trash",
+    "at", "scheduled", "cancel", "sort", "head", "tail", "prompt", "meminfo", "clip", "paste", "info", "version",
+];
+
+/// Tab-completion state kept between consecutive Tab presses
+struct Completion {
+    start: usize,            // byte offset of the token being completed
+    candidates: Vec<String>, // sorted matches for the original prefix
+    index: Option<usize>,    // None until the first cycling Tab
+}
+
 pub struct Terminal {
     cursor_x: usize,
     cursor_y: usize,
+    top: usize,        // first row the terminal draws on; row 0 is the status line when 1
+    input_row: usize,  // screen row where the prompt starts
+    input_rows: usize, // rows the prompt and input took at the last redraw
     input: String,
+    input_cursor: usize, // byte offset of the edit cursor in `input`
     prompt: String,
+    history: Vec<String>,
+    history_pos: Option<usize>,
+    completion: Option<Completion>,
+    scrollback: VecDeque<Row>,
+    view_offset: usize,    // lines scrolled back from the live screen, 0 = live
+    live_screen: Vec<Row>, // the live screen, saved while viewing scrollback
+    theme: Theme,
 }
 
 impl Terminal {
-    fn new(prompt: &str) -> Self {
+    pub(crate) fn new(prompt: &str) -> Self {
         Self {
             cursor_x: 0,
             cursor_y: 0,
+            top: 0,
+            input_row: 0,
+            input_rows: 1,
             input: String::new(),
+            input_cursor: 0,
             prompt: prompt.to_string(),
+            history: Vec::new(),
+            history_pos: None,
+            completion: None,
+            scrollback: VecDeque::new(),
+            view_offset: 0,
+            live_screen: Vec::new(),
+            theme: THEMES[0],
         }
     }
 
     fn clear_screen(&mut self) {
         unsafe {
-            for i in 0..(WIDTH*HEIGHT*2) {
+            for i in (self.top*WIDTH*2)..(WIDTH*HEIGHT*2) {
                 VGA_BUFFER.add(i).write_volatile(0);
             }
         }
         self.cursor_x = 0;
-        self.cursor_y = 0;
+        self.cursor_y = self.top;
+    }
+
+    /// Rows the terminal draws on, below the status line if there is one
+    fn rows(&self) -> usize {
+        HEIGHT - self.top
+    }
+
+    /// Hand row 0 over to a background task such as the clock.
+    ///
+    /// The row's text moves to the scrollback, so nothing is lost; from then
+    /// on writing, scrolling and the scrollback view leave row 0 alone.
+    fn reserve_status_line(&mut self) {
+        if self.top == 1 {
+            return;
+        }
+        self.snap_to_bottom();
+        if self.scrollback.len() == SCROLLBACK_LINES {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(Self::read_row(0));
+        self.blank_row(0);
+        self.top = 1;
+        if self.cursor_y == 0 {
+            self.cursor_y = 1;
+            self.move_cursor();
+        }
+    }
+
+    /// Give row 0 back to the terminal once nothing draws on it any more
+    fn release_status_line(&mut self) {
+        if self.top == 0 {
+            return;
+        }
+        self.top = 0;
+        self.blank_row(0);
+    }
+
+    fn blank_row(&self, y: usize) {
+        for x in 0..WIDTH {
+            let offset = 2 * (y * WIDTH + x);
+            unsafe {
+                VGA_BUFFER.add(offset).write_volatile(b' ');
+                VGA_BUFFER.add(offset + 1).write_volatile(self.theme.text.as_u8());
+            }
+        }
+    }
+
+    /// Clear the screen and draw a fresh, empty prompt at the top-left.
+    ///
+    /// Use this instead of `clear_screen` whenever the prompt is redrawn after
+    /// clearing, so the input row and cursor can't drift apart. Any leftover
+    /// input (such as the command that triggered the reset) is discarded.
+    fn reset_screen(&mut self) {
+        self.view_offset = 0;
+        self.live_screen.clear();
+        self.clear_screen();
+        self.input.clear();
+        self.input_cursor = 0;
+        self.history_pos = None;
+        self.start_input_here();
+        self.redraw_input();
     }
 
     fn move_cursor(&self) {
@@ -59,6 +216,10 @@ impl Terminal {
     }
 
     pub fn write_char(&mut self, c: char) {
+        self.write_colored_char(c, self.theme.text);
+    }
+
+    pub fn write_colored_char(&mut self, c: char, color: ColorCode) {
         match c {
             '\n' => {
                 self.cursor_x = 0;
@@ -69,10 +230,12 @@ impl Terminal {
                 }
             }
             _ => {
+                // Characters with no glyph, like '\r', take no cell
+                let Some(byte) = to_cp437(c) else { return };
                 let offset = 2 * (self.cursor_y * WIDTH + self.cursor_x);
                 unsafe {
-                    VGA_BUFFER.add(offset).write_volatile(c as u8);
-                    VGA_BUFFER.add(offset + 1).write_volatile(0x0f);
+                    VGA_BUFFER.add(offset).write_volatile(byte);
+                    VGA_BUFFER.add(offset + 1).write_volatile(color.as_u8());
                 }
                 self.cursor_x += 1;
                 if self.cursor_x >= WIDTH {
@@ -94,47 +257,361 @@ impl Terminal {
         }
     }
 
-    fn redraw_input(&mut self) {
-        let line = format!("{}{}", self.prompt, self.input);
+    pub fn write_colored_str(&mut self, s: &str, color: ColorCode) {
+        for c in s.chars() {
+            self.write_colored_char(c, color);
+        }
+    }
+
+    /// Switch to another theme and recolor the whole screen with it
+    fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        for i in (self.top * WIDTH)..(WIDTH * HEIGHT) {
+            unsafe { VGA_BUFFER.add(2 * i + 1).write_volatile(theme.text.as_u8()) };
+        }
+        self.redraw_input();
+    }
+
+    /// Blank the cursor's row and return to its first column
+    fn clear_line(&mut self) {
         for i in 0..WIDTH {
             let offset = 2 * (self.cursor_y * WIDTH + i);
             unsafe {
-                if i < line.len() {
-                    VGA_BUFFER.add(offset).write_volatile(line.as_bytes()[i]);
-                    VGA_BUFFER.add(offset+1).write_volatile(0x0f);
-                } else {
-                    VGA_BUFFER.add(offset).write_volatile(b' ');
-                    VGA_BUFFER.add(offset+1).write_volatile(0x0f);
-                }
+                VGA_BUFFER.add(offset).write_volatile(b' ');
+                VGA_BUFFER.add(offset + 1).write_volatile(self.theme.text.as_u8());
             }
         }
-        self.cursor_x = line.len();
+        self.cursor_x = 0;
         self.move_cursor();
     }
 
+    /// Draw the prompt and input from `input_row`, wrapping onto the rows below
+    /// and scrolling the screen when the input runs past the bottom
+    fn redraw_input(&mut self) {
+        let prompt_len = self.prompt.chars().count();
+        let line: Vec<char> = self.prompt.chars().chain(self.input.chars()).collect();
+        // One extra cell so the cursor can sit after the last character
+        let rows = (line.len() / WIDTH + 1).min(self.rows());
+        while self.input_row + rows > HEIGHT {
+            self.scroll_up();
+            self.input_row -= 1;
+        }
+
+        // Also blank rows left over from a longer input
+        let drawn = rows.max(self.input_rows).min(HEIGHT - self.input_row);
+        for i in 0..drawn * WIDTH {
+            let offset = 2 * (self.input_row * WIDTH + i);
+            let color = if i < prompt_len { self.theme.prompt } else { self.theme.text };
+            let byte = line.get(i).and_then(|c| to_cp437(*c)).unwrap_or(b' ');
+            unsafe {
+                VGA_BUFFER.add(offset).write_volatile(byte);
+                VGA_BUFFER.add(offset + 1).write_volatile(color.as_u8());
+            }
+        }
+        self.input_rows = rows;
+
+        let cursor = prompt_len + self.input[..self.input_cursor].chars().count();
+        self.cursor_y = (self.input_row + cursor / WIDTH).min(HEIGHT - 1);
+        self.cursor_x = cursor % WIDTH;
+        self.move_cursor();
+    }
+
+    /// Start a new prompt on the cursor's row
+    fn start_input_here(&mut self) {
+        self.input_row = self.cursor_y;
+        self.input_rows = 1;
+    }
+
+    /// Move the cursor to the start of the row below the input
+    fn finish_input(&mut self) {
+        self.cursor_y = self.input_row + self.input_rows - 1;
+        self.write_char('\n');
+    }
+
+    /// Insert a character at the input cursor
     fn push(&mut self, c: char) {
-        self.input.push(c);
+        self.input.insert(self.input_cursor, c);
+        self.input_cursor += c.len_utf8();
         self.redraw_input();
     }
 
+    /// Delete the character before the input cursor (Backspace)
     fn pop(&mut self) {
-        if self.input.pop().is_some() {
+        if let Some(c) = self.input[..self.input_cursor].chars().next_back() {
+            self.input_cursor -= c.len_utf8();
+            self.input.remove(self.input_cursor);
+            self.redraw_input();
+        }
+    }
+
+    /// Delete the character under the input cursor (Delete)
+    fn delete(&mut self) {
+        if self.input_cursor < self.input.len() {
+            self.input.remove(self.input_cursor);
             self.redraw_input();
         }
     }
 
+    /// Replace the whole input line, placing the cursor at its end
+    fn set_input(&mut self, text: String) {
+        self.input = text;
+        self.input_cursor = self.input.len();
+        self.redraw_input();
+    }
+
     fn clear_input(&mut self) {
+        self.start_input_here();
         self.input.clear();
+        self.input_cursor = 0;
+        self.history_pos = None;
         self.redraw_input();
     }
 
+    fn move_input_cursor_left(&mut self) {
+        if let Some(c) = self.input[..self.input_cursor].chars().next_back() {
+            self.input_cursor -= c.len_utf8();
+            self.redraw_input();
+        }
+    }
+
+    fn move_input_cursor_right(&mut self) {
+        if let Some(c) = self.input[self.input_cursor..].chars().next() {
+            self.input_cursor += c.len_utf8();
+            self.redraw_input();
+        }
+    }
+
+    fn move_input_cursor_home(&mut self) {
+        self.input_cursor = 0;
+        self.redraw_input();
+    }
+
+    fn move_input_cursor_end(&mut self) {
+        self.input_cursor = self.input.len();
+        self.redraw_input();
+    }
+
+    /// Byte offset of the start of the word before the cursor
+    fn word_left_boundary(&self) -> usize {
+        let before = self.input[..self.input_cursor].trim_end();
+        before
+            .char_indices()
+            .rev()
+            .find(|(_, c)| c.is_whitespace())
+            .map(|(i, c)| i + c.len_utf8())
+            .unwrap_or(0)
+    }
+
+    /// Move the cursor to the start of the previous word (Ctrl+Left)
+    fn move_input_cursor_word_left(&mut self) {
+        self.input_cursor = self.word_left_boundary();
+        self.redraw_input();
+    }
+
+    /// Move the cursor to the end of the next word (Ctrl+Right)
+    fn move_input_cursor_word_right(&mut self) {
+        let after = &self.input[self.input_cursor..];
+        let rest = after.trim_start();
+        let word = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.input_cursor += (after.len() - rest.len()) + word;
+        self.redraw_input();
+    }
+
+    /// Delete the word before the cursor (Ctrl+Backspace)
+    fn delete_word(&mut self) {
+        let start = self.word_left_boundary();
+        self.input.replace_range(start..self.input_cursor, "");
+        self.input_cursor = start;
+        self.redraw_input();
+    }
+
+    /// Replace the token from `start` up to the cursor with `text`
+    fn replace_token(&mut self, start: usize, text: &str) {
+        self.input.replace_range(start..self.input_cursor, text);
+        self.input_cursor = start + text.len();
+        self.redraw_input();
+    }
+
+    /// Complete the token before the cursor: command names first, then entries of `cwd`.
+    ///
+    /// A single match is completed directly. With several matches the first Tab
+    /// lists them and every further Tab cycles through them in sorted order.
+    fn autocomplete(&mut self, cwd: &Directory) {
+        if let Some(state) = self.completion.as_mut() {
+            let next = state.index.map(|i| (i + 1) % state.candidates.len()).unwrap_or(0);
+            state.index = Some(next);
+            let (start, text) = (state.start, state.candidates[next].clone());
+            self.replace_token(start, &text);
+            return;
+        }
+
+        let start = self.input[..self.input_cursor].rfind(' ').map(|i| i + 1).unwrap_or(0);
+        let prefix = &self.input[start..self.input_cursor];
+
+        let mut candidates: Vec<String> = if start == 0 {
+            COMMANDS.iter().filter(|c| c.starts_with(prefix)).map(|c| c.to_string()).collect()
+        } else {
+            cwd.list_subdirs().into_iter()
+                .chain(cwd.list_files())
+                .filter(|n| n.starts_with(prefix) && (prefix.starts_with('.') || !n.starts_with('.')))
+                .collect()
+        };
+        candidates.sort();
+        candidates.dedup();
+
+        match candidates.len() {
+            0 => {}
+            1 => self.replace_token(start, &candidates[0]),
+            _ => {
+                self.finish_input();
+                self.write_str(&candidates.join("  "));
+                self.write_char('\n');
+                self.start_input_here();
+                self.redraw_input();
+                self.completion = Some(Completion { start, candidates, index: None });
+            }
+        }
+    }
+
+    /// Remember a command, skipping empty lines and consecutive duplicates
+    fn history_push(&mut self, cmd: &str) {
+        if cmd.is_empty() || self.history.last().map(|l| l == cmd).unwrap_or(false) {
+            return;
+        }
+        self.history.push(cmd.to_string());
+        if self.history.len() > HISTORY_LIMIT {
+            let excess = self.history.len() - HISTORY_LIMIT;
+            self.history.drain(..excess);
+        }
+    }
+
+    /// Expand a leading `!!` (last command) or `!N` (entry N as numbered by
+    /// `history`); any other input is returned unchanged
+    fn history_expand(&self, input: &str) -> Result<String, String> {
+        let (first, rest) = input.split_once(' ').unwrap_or((input, ""));
+        let entry = match first {
+            "!!" => self.history.last(),
+            _ => match first.strip_prefix('!') {
+                Some(n) => n.parse::<usize>().ok()
+                    .and_then(|n| n.checked_sub(1))
+                    .and_then(|i| self.history.get(i)),
+                None => return Ok(input.to_string()),
+            },
+        };
+        match entry {
+            Some(cmd) if rest.is_empty() => Ok(cmd.clone()),
+            Some(cmd) => Ok(format!("{} {}", cmd, rest)),
+            None => Err(format!("{}: no such history entry\n", first)),
+        }
+    }
+
+    /// Recall the previous command (arrow up)
+    fn history_prev(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let pos = match self.history_pos {
+            Some(p) => p.saturating_sub(1),
+            None => self.history.len() - 1,
+        };
+        self.history_pos = Some(pos);
+        self.set_input(self.history[pos].clone());
+    }
+
+    /// Recall the next command (arrow down), ending on an empty line
+    fn history_next(&mut self) {
+        match self.history_pos {
+            Some(p) if p + 1 < self.history.len() => {
+                self.history_pos = Some(p + 1);
+                self.set_input(self.history[p + 1].clone());
+            }
+            Some(_) => {
+                self.history_pos = None;
+                self.set_input(String::new());
+            }
+            None => {}
+        }
+    }
+
     fn get_input(&self) -> &str {
         &self.input
     }
 
+    fn read_row(y: usize) -> Row {
+        let mut row = [0u8; WIDTH * 2];
+        for (i, b) in row.iter_mut().enumerate() {
+            *b = unsafe { VGA_BUFFER.add(2 * y * WIDTH + i).read_volatile() };
+        }
+        row
+    }
+
+    fn write_row(y: usize, row: &Row) {
+        for (i, b) in row.iter().enumerate() {
+            unsafe { VGA_BUFFER.add(2 * y * WIDTH + i).write_volatile(*b) };
+        }
+    }
+
+    /// Scroll the view one page back into the scrollback buffer
+    fn page_up(&mut self) {
+        if self.scrollback.is_empty() {
+            return;
+        }
+        if self.view_offset == 0 {
+            self.live_screen = (self.top..HEIGHT).map(Self::read_row).collect();
+        }
+        self.view_offset = (self.view_offset + self.rows() - 1).min(self.scrollback.len());
+        self.render_view();
+    }
+
+    /// Scroll the view one page towards the live screen
+    fn page_down(&mut self) {
+        if self.view_offset == 0 {
+            return;
+        }
+        if self.view_offset < self.rows() {
+            self.snap_to_bottom();
+        } else {
+            self.view_offset -= self.rows() - 1;
+            self.render_view();
+        }
+    }
+
+    /// Return from the scrollback view to the live screen
+    fn snap_to_bottom(&mut self) {
+        if self.view_offset == 0 {
+            return;
+        }
+        self.view_offset = 0;
+        for (y, row) in self.live_screen.iter().enumerate() {
+            Self::write_row(self.top + y, row);
+        }
+        self.live_screen.clear();
+        self.move_cursor();
+    }
+
+    /// Draw the scrollback lines followed by the live screen, `view_offset` lines up
+    fn render_view(&self) {
+        let first = self.scrollback.len() - self.view_offset;
+        for y in 0..self.rows() {
+            let idx = first + y;
+            let row = if idx < self.scrollback.len() {
+                &self.scrollback[idx]
+            } else {
+                &self.live_screen[idx - self.scrollback.len()]
+            };
+            Self::write_row(self.top + y, row);
+        }
+    }
+
     fn scroll_up(&mut self) {
+        // Keep the top line before it scrolls away
+        if self.scrollback.len() == SCROLLBACK_LINES {
+            self.scrollback.pop_front();
+        }
+        self.scrollback.push_back(Self::read_row(self.top));
+
         unsafe {
-            for y in 1..HEIGHT {
+            for y in (self.top + 1)..HEIGHT {
                 for x in 0..WIDTH {
                     let from = 2 * (y * WIDTH + x);
                     let to = 2 * ((y-1) * WIDTH + x);
@@ -145,7 +622,7 @@ impl Terminal {
             for x in 0..WIDTH {
                 let offset = 2 * ((HEIGHT-1) * WIDTH + x);
                 VGA_BUFFER.add(offset).write_volatile(b' ');
-                VGA_BUFFER.add(offset+1).write_volatile(0x0f);
+                VGA_BUFFER.add(offset+1).write_volatile(self.theme.text.as_u8());
             }
         }
     }
@@ -154,51 +631,184 @@ impl Terminal {
 /// Main REPL
 pub async fn katalyst_repl() {
     let mut scancodes = ScancodeStream::new();
-    let mut keyboard = Keyboard::new(ScancodeSet1::new(), layouts::Us104Key, HandleControl::Ignore);
+    // Settings below live in the REPL and last until reboot; ROOT_DIR is
+    // memory-only too, so keeping them in files wouldn't outlive a reboot
+    let mut layout_name = "us";
+    let mut prompt_template = DEFAULT_PROMPT.to_string();
+    let mut keyboard = new_keyboard(AnyLayout::Us104Key(layouts::Us104Key));
 
     let mut term = Terminal::new("");
     term.clear_screen();
 
-    term.write_str("katalyst v0.1\n");
+    term.write_str(&format!("katalyst v{}\n", crate::sys::VERSION));
     term.write_str("a simple OS kernel, made by kewl.\n\n");
 
     let mut cwd_path: Vec<&'static str> = vec!["main"];
+    let mut prev_cwd_path: Option<Vec<&'static str>> = None;
+
+    let mut aliases: BTreeMap<String, String> = BTreeMap::new();
+    let mut vars: BTreeMap<String, String> = BTreeMap::new();
+    let mut bindings = default_bindings();
+
+    // Background tasks started with `spawn`
+    let mut jobs: Vec<(TaskId, &'static str)> = Vec::new();
+
+    // One-shot commands queued with `at`: (id, fire tick, command line)
+    let mut scheduled: Vec<(u64, u64, String)> = Vec::new();
+    let mut next_job_id = 1;
+    // Input that was being typed when a scheduled command fired
+    let mut held_input: Option<String> = None;
+
+    // HandleControl::Ignore hides Ctrl from decoded keys, so track it here
+    let mut ctrl_held = false;
 
     loop {
+        // The working directory may have been removed, e.g. via a stale `-> -`
+        if find_cwd(&ROOT_DIR.lock(), &cwd_path).is_none() {
+            term.write_str(&format!("Warning: '{}' no longer exists, returning to main\n", cwd_path.join("/")));
+            cwd_path = vec!["main"];
+        }
         term.clear_input();
-        update_prompt(&mut term, &cwd_path);
+        update_prompt(&mut term, &prompt_template, &cwd_path);
+        if let Some(text) = held_input.take() {
+            term.set_input(text);
+        }
         term.move_cursor();
 
-        // Read input
+        // Read input, unless a scheduled command comes due first
+        let mut fired: Option<String> = None;
         loop {
-            if let Some(scancode) = scancodes.next().await {
+            let next = match scheduled.iter().map(|(_, at, _)| *at).min() {
+                Some(deadline) => match select(scancodes.next(), sleep_until(deadline)).await {
+                    Either::Left((scancode, _)) => scancode,
+                    Either::Right(_) => None,
+                },
+                None => scancodes.next().await,
+            };
+            let now = crate::sys::UPTIME_TICKS.load(core::sync::atomic::Ordering::Relaxed);
+            if let Some(pos) = scheduled.iter().position(|(_, at, _)| *at <= now) {
+                let (id, _, line) = scheduled.remove(pos);
+                held_input = Some(term.get_input().to_string());
+                term.snap_to_bottom();
+                term.finish_input();
+                term.write_str(&format!("[at {}] {}\n", id, line));
+                fired = Some(line);
+                break;
+            }
+            if let Some(scancode) = next {
                 if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
+                    if matches!(key_event.code, KeyCode::LControl | KeyCode::RControl) {
+                        ctrl_held = key_event.state == KeyState::Down;
+                    }
                     if let Some(key) = keyboard.process_keyevent(key_event) {
+                        // Any key other than Tab ends a completion cycle
+                        if key != DecodedKey::Unicode('\t') {
+                            term.completion = None;
+                        }
+                        // Typing while viewing scrollback returns to the live screen
+                        if !matches!(key, DecodedKey::RawKey(KeyCode::PageUp) | DecodedKey::RawKey(KeyCode::PageDown)) {
+                            term.snap_to_bottom();
+                        }
                         match key {
                             DecodedKey::Unicode(c) => match c {
-                                '\n' | '\r' => { term.cursor_x = 0; term.cursor_y += 1; term.move_cursor(); break; }
+                                '\t' => {
+                                    let root = ROOT_DIR.lock();
+                                    term.autocomplete(resolve_cwd(&root, &cwd_path));
+                                }
+                                '\n' | '\r' => { term.finish_input(); break; }
+                                '\x08' if ctrl_held => term.delete_word(),
+                                'v' | 'V' if ctrl_held => {
+                                    if let Some(line) = crate::clipboard::first_line() {
+                                        for c in line.chars() {
+                                            term.push(c);
+                                        }
+                                    }
+                                }
                                 '\x08' => term.pop(),
+                                '\x7f' => term.delete(),
                                 _ => term.push(c),
                             },
-                            DecodedKey::RawKey(_) => {}
+                            DecodedKey::RawKey(KeyCode::ArrowLeft) if ctrl_held => term.move_input_cursor_word_left(),
+                            DecodedKey::RawKey(KeyCode::ArrowRight) if ctrl_held => term.move_input_cursor_word_right(),
+                            DecodedKey::RawKey(KeyCode::ArrowLeft) => term.move_input_cursor_left(),
+                            DecodedKey::RawKey(KeyCode::ArrowRight) => term.move_input_cursor_right(),
+                            DecodedKey::RawKey(KeyCode::Home) => term.move_input_cursor_home(),
+                            DecodedKey::RawKey(KeyCode::End) => term.move_input_cursor_end(),
+                            DecodedKey::RawKey(KeyCode::ArrowUp) => term.history_prev(),
+                            DecodedKey::RawKey(KeyCode::ArrowDown) => term.history_next(),
+                            DecodedKey::RawKey(KeyCode::PageUp) => term.page_up(),
+                            DecodedKey::RawKey(KeyCode::PageDown) => term.page_down(),
+                            DecodedKey::RawKey(code) => {
+                                if let Some(text) = fkey_name(code).and_then(|k| bindings.get(k)) {
+                                    for c in text.chars() {
+                                        term.push(c);
+                                    }
+                                }
+                            }
                         }
                     }
                 }
             }
         }
 
-        let input = term.get_input().trim().to_string();
+        // Scheduled commands stay out of history. Their $VARs were expanded when
+        // the `at` line ran, so only alias expansion applies when they fire.
+        let from_schedule = fired.is_some();
+        let input = match fired {
+            Some(line) => line,
+            None => {
+                let raw = term.get_input().trim().to_string();
+                let input = match term.history_expand(&raw) {
+                    Ok(line) => line,
+                    Err(msg) => {
+                        term.write_str(&msg);
+                        continue;
+                    }
+                };
+                // Show what a history reference ran
+                if input != raw {
+                    term.write_str(&format!("{}\n", input));
+                }
+                input
+            }
+        };
+        if !input.is_empty() && !from_schedule {
+            term.history_push(&input);
+        }
+        let input = match expand_alias(&aliases, &input) {
+            Some(line) => line,
+            None => {
+                term.write_str("Alias expansion loop detected\n");
+                continue;
+            }
+        };
+        let input = if from_schedule { input } else { expand_vars(&mut term, &vars, &cwd_path, &input) };
         let mut parts = input.split_whitespace();
         let command = parts.next().unwrap_or("");
         let arg = parts.next();
+        let arg2 = parts.next();
+
+        // These store the rest of the line, pipes included
+        if input.contains('|') && !matches!(command, "alias" | "set" | "bind" | "at" | "prompt" | "clip") {
+            let result = run_pipeline(&ROOT_DIR.lock(), &cwd_path, &input);
+            match result {
+                Ok(text) => page_output(&mut term, &mut scancodes, &mut keyboard, &text).await,
+                Err(msg) => term.write_str(&msg),
+            }
+            continue;
+        }
 
         match command {
             "help" => {
                 // All lines are &'static str
-                let help_text: [&'static str; 3] = [
-                    "System commands: core, halt, reboot, spark",
-                    "File commands: make file/folder, del file/folder, peek folder, move source -> dest",
-                    "Other file commands: push filename content, pull filename, link source dest, clone source dest",
+                let help_text: [&'static str; 7] = [
+                    "System commands: info, core, meminfo, monitor, date, spawn task, jobs, kill task_id, at +delay cmd, scheduled, cancel job_id, theme name, layout name, halt, shutdown, reboot, spark",
+                    "File commands: make [-p] file/folder, touch file, del [-r] file/folder, undelete name, trash, empty-trash, lock/unlock file, peek [-l] [-x] [--no-pager] folder|file[:N-M], mv source dest, cp source dest",
+                    "Other file commands: export folder file, import file folder, push filename content, pull filename, link source dest, clone source dest",
+                    "Navigation: -> path, -> - (previous), back, <-, here",
+                    "Info commands: stat name, wc file, head/tail file [N], sort [-r] [-u] [-w] file, diff a b, fsck [-f], du [folder], find pattern, seek [-i] [-r] pattern",
+                    "Shell commands: history [clear], !!, !N, alias [name=expansion], unalias name, set NAME value, unset NAME, env, bind key text, unbind key, prompt template, clip [text], paste, cmd | cmd (see man pipe)",
+                    "Use 'man command' for details on a command, or 'man' to list them",
                 ];
 
                 // Print each line followed by a newline
@@ -208,15 +818,308 @@ pub async fn katalyst_repl() {
                 }
             }
 
-            "wipe" | "wp" => term.clear_screen(),
+            "man" => match arg {
+                Some(name) => match man_page(name) {
+                    Some(page) => {
+                        term.write_str(page);
+                        term.write_char('\n');
+                    }
+                    None => term.write_str(&format!("No manual entry for '{}'\n", name)),
+                },
+                None => {
+                    let documented: Vec<&str> = COMMANDS.iter().copied().filter(|c| man_page(c).is_some()).collect();
+                    term.write_str(&format!("Documented commands: {}\n", documented.join(", ")));
+                }
+            },
+
+            "wipe" | "wp" => term.reset_screen(),
             "halt" => crate::sys::halt(&mut term),
+            "shutdown" => crate::sys::shutdown(&mut term),
             "reboot" => crate::sys::reboot(&mut term),
             "spark" => crate::sys::spark(&mut term),
             "core" => crate::sys::core_report(&mut term),
+            "meminfo" => crate::sys::meminfo(&mut term),
+            "info" | "version" => crate::sys::info(&mut term),
+            "monitor" => run_monitor(&mut term, &mut scancodes, &mut keyboard).await,
+            "date" => term.write_str(&format!("{}\n", crate::rtc::read_rtc())),
+            "layout" => match arg.map(|a| (a, layout_by_name(a))) {
+                Some((name, Some(layout))) => {
+                    keyboard = new_keyboard(layout);
+                    layout_name = LAYOUTS.iter().copied().find(|l| *l == name).unwrap_or("us");
+                    term.write_str(&format!("Keyboard layout: {}\n", layout_name));
+                }
+                Some((name, None)) => {
+                    term.write_str(&format!("Unknown layout '{}'. Available: {}\n", name, LAYOUTS.join(", ")));
+                }
+                None => {
+                    term.write_str(&format!("Current layout: {}\n", layout_name));
+                    term.write_str(&format!("Available: {}\n", LAYOUTS.join(", ")));
+                }
+            },
+
+            "theme" => match arg.and_then(|a| THEMES.iter().find(|t| t.name == a)) {
+                Some(theme) => term.set_theme(*theme),
+                None => {
+                    let names: Vec<&str> = THEMES.iter().map(|t| t.name).collect();
+                    term.write_str(&format!("Current theme: {}\n", term.theme.name));
+                    term.write_str(&format!("Available themes: {}\n", names.join(", ")));
+                }
+            },
+            "alias" => {
+                let spec = input["alias".len()..].trim();
+                if spec.is_empty() {
+                    if aliases.is_empty() {
+                        term.write_str("No aliases defined\n");
+                    }
+                    for (name, expansion) in aliases.iter() {
+                        term.write_str(&format!("{}={}\n", name, expansion));
+                    }
+                } else if let Some((name, expansion)) = spec.split_once('=') {
+                    let (name, expansion) = (name.trim(), expansion.trim());
+                    if name.is_empty() || name.contains(char::is_whitespace) || expansion.is_empty() {
+                        term.write_str("Usage: alias name=expansion\n");
+                    } else {
+                        aliases.insert(name.to_string(), expansion.to_string());
+                    }
+                } else {
+                    match aliases.get(spec) {
+                        Some(expansion) => term.write_str(&format!("{}={}\n", spec, expansion)),
+                        None => term.write_str(&format!("No alias '{}'\n", spec)),
+                    }
+                }
+            }
+
+            "unalias" => {
+                if let Some(name) = arg {
+                    if aliases.remove(name).is_none() {
+                        term.write_str(&format!("No alias '{}'\n", name));
+                    }
+                } else { term.write_str("Usage: unalias <name>\n"); }
+            }
+
+            "history" => match arg {
+                Some("clear") => {
+                    term.history.clear();
+                    term.history_pos = None;
+                        }
+                Some(_) => term.write_str("Usage: history [clear]\n"),
+                None => {
+                    let first = term.history.len().saturating_sub(HISTORY_SHOWN);
+                    let mut listing = String::new();
+                    for (i, cmd) in term.history.iter().enumerate().skip(first) {
+                        listing.push_str(&format!("{:>4}  {}\n", i + 1, cmd));
+                    }
+                    term.write_str(&listing);
+                }
+            },
+
+            "set" => {
+                let spec = input["set".len()..].trim();
+                match spec.split_once(char::is_whitespace) {
+                    Some((name, value)) if is_var_name(name) => {
+                        if BUILTIN_VARS.contains(&name) {
+                            term.write_str(&format!("'{}' is read-only\n", name));
+                        } else {
+                            vars.insert(name.to_string(), value.trim().to_string());
+                        }
+                    }
+                    _ => term.write_str("Usage: set NAME value\n"),
+                }
+            }
+
+            "unset" => {
+                if let Some(name) = arg {
+                    if BUILTIN_VARS.contains(&name) {
+                        term.write_str(&format!("'{}' is read-only\n", name));
+                    } else if vars.remove(name).is_none() {
+                        term.write_str(&format!("No variable '{}'\n", name));
+                    }
+                } else { term.write_str("Usage: unset <name>\n"); }
+            }
+
+            "env" => {
+                for name in BUILTIN_VARS.iter() {
+                    let value = var_value(&vars, &cwd_path, name).unwrap_or_default();
+                    term.write_str(&format!("{}={}\n", name, value));
+                }
+                for (name, value) in vars.iter() {
+                    term.write_str(&format!("{}={}\n", name, value));
+                }
+            }
+
+            "bind" => {
+                let spec = input["bind".len()..].trim();
+                let (key, text) = spec.split_once(char::is_whitespace).unwrap_or((spec, ""));
+                let key = key.to_ascii_uppercase();
+                // Quotes allow leading/trailing spaces in the inserted text
+                let text = text.trim();
+                let text = text.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(text);
+                if key.is_empty() {
+                    if bindings.is_empty() {
+                        term.write_str("No key bindings\n");
+                    }
+                    for (key, text) in bindings.iter() {
+                        term.write_str(&format!("{} \"{}\"\n", key, text));
+                    }
+                } else if !(1..=12).any(|n| key == format!("F{}", n)) {
+                    term.write_str("Only function keys F1-F12 can be bound\n");
+                } else if text.is_empty() {
+                    match bindings.get(&key) {
+                        Some(text) => term.write_str(&format!("{} \"{}\"\n", key, text)),
+                        None => term.write_str(&format!("{} is not bound\n", key)),
+                    }
+                } else {
+                    bindings.insert(key, text.to_string());
+                }
+            }
+
+            "prompt" => {
+                let spec = input["prompt".len()..].trim();
+                // Quotes keep the trailing space most prompts end with
+                let spec = spec.strip_prefix('"').and_then(|t| t.strip_suffix('"')).unwrap_or(spec);
+                match spec {
+                    "" => term.write_str(&format!("\"{}\"\n", prompt_template)),
+                    "reset" => prompt_template = DEFAULT_PROMPT.to_string(),
+                    template => prompt_template = template.to_string(),
+                }
+            }
+
+            "clip" => {
+                let text = input["clip".len()..].trim();
+                if text.is_empty() {
+                    let lines = crate::clipboard::lines();
+                    if lines.is_empty() {
+                        term.write_str("Clipboard is empty\n");
+                    }
+                    for line in lines {
+                        term.write_str(&format!("{}\n", line));
+                    }
+                } else {
+                    crate::clipboard::set(text);
+                }
+            }
+
+            // Put the clipboard's first line in the next prompt, ready to edit
+            "paste" => match crate::clipboard::first_line() {
+                Some(line) => held_input = Some(line),
+                None => term.write_str("Clipboard is empty\n"),
+            },
+
+            "unbind" => {
+                if let Some(key) = arg {
+                    if bindings.remove(&key.to_ascii_uppercase()).is_none() {
+                        term.write_str(&format!("{} is not bound\n", key));
+                    }
+                } else { term.write_str("Usage: unbind <key>\n"); }
+            }
+
+            "spawn" => match arg.and_then(|a| BACKGROUND_TASKS.iter().copied().find(|t| *t == a)) {
+                Some(name) => {
+                    let mut exec = EXECUTOR.lock();
+                    jobs.retain(|(id, _)| exec.task_ids().any(|t| t == id));
+                    if let Some((id, _)) = jobs.iter().find(|(_, n)| *n == name) {
+                        term.write_str(&format!("'{}' is already running as task {}\n", name, id));
+                    } else if let Some(task) = background_task(name) {
+                        if name == "clock" {
+                            term.reserve_status_line();
+                        }
+                        let id = task.id();
+                        exec.spawn(task);
+                        jobs.push((id, name));
+                        term.write_str(&format!("Started '{}' as task {}\n", name, id));
+                    }
+                }
+                None => term.write_str(&format!("Usage: spawn <task>. Available: {}\n", BACKGROUND_TASKS.join(", "))),
+            },
+
+            "jobs" => {
+                let exec = EXECUTOR.lock();
+                jobs.retain(|(id, _)| exec.task_ids().any(|t| t == id));
+                if jobs.is_empty() {
+                    term.write_str("No background tasks\n");
+                }
+                for (id, name) in jobs.iter() {
+                    term.write_str(&format!("{:>4}  {}\n", id, name));
+                }
+            }
+
+            "kill" => match arg.and_then(|a| a.parse::<u64>().ok()) {
+                Some(id) => {
+                    let mut exec = crate::task::executor::EXECUTOR.lock();
+                    let task = exec.task_ids().find(|t| t.value() == id).copied();
+                    match task {
+                        Some(t) if exec.current_task() == Some(t) => {
+                            term.write_str("Refusing to kill the REPL's own task\n");
+                        }
+                        Some(t) if exec.kill(t) => {
+                            jobs.retain(|(job, _)| *job != t);
+                            if !jobs.iter().any(|(_, name)| *name == "clock") {
+                                term.release_status_line();
+                            }
+                            term.write_str(&format!("Killed task {}\n", id));
+                        }
+                        _ => term.write_str(&format!("No task with ID {}\n", id)),
+                    }
+                }
+                None => term.write_str("Usage: kill <task_id>\n"),
+            },
+            "at" => {
+                let now = crate::sys::UPTIME_TICKS.load(core::sync::atomic::Ordering::Relaxed);
+                // Delays too long to count in ticks are rejected like malformed ones
+                let delay = arg.and_then(parse_delay).and_then(|secs| {
+                    let fire = secs.checked_mul(crate::sys::ticks_per_second())?.checked_add(now)?;
+                    Some((secs, fire))
+                });
+                match delay {
+                    Some((secs, fire)) => {
+                        let line = input.split_whitespace().skip(2).collect::<Vec<_>>().join(" ");
+                        if line.is_empty() {
+                            term.write_str("Usage: at +<delay> <command>\n");
+                        } else {
+                            term.write_str(&format!("Job {} runs in {}s: {}\n", next_job_id, secs, line));
+                            scheduled.push((next_job_id, fire, line));
+                            next_job_id += 1;
+                        }
+                    }
+                    None => term.write_str("Usage: at +<delay> <command>, delay like 30s, 5m or 1h\n"),
+                }
+            }
+
+            "scheduled" => {
+                if scheduled.is_empty() {
+                    term.write_str("No scheduled commands\n");
+                }
+                let now = crate::sys::UPTIME_TICKS.load(core::sync::atomic::Ordering::Relaxed);
+                let tps = crate::sys::ticks_per_second();
+                scheduled.sort_by_key(|(_, at, _)| *at);
+                for (id, at, line) in scheduled.iter() {
+                    let left = at.saturating_sub(now).div_ceil(tps);
+                    term.write_str(&format!("{:>4}  in {:>5}s  {}\n", id, left, line));
+                }
+            }
+
+            "cancel" => match arg.and_then(|a| a.parse::<u64>().ok()) {
+                Some(id) => match scheduled.iter().position(|(job, _, _)| *job == id) {
+                    Some(pos) => {
+                        let (_, _, line) = scheduled.remove(pos);
+                        term.write_str(&format!("Cancelled job {}: {}\n", id, line));
+                    }
+                    None => term.write_str(&format!("No scheduled job {}\n", id)),
+                },
+                None => term.write_str("Usage: cancel <job_id>\n"),
+            },
+
             "here" => {
                 term.write_str(&format!("Current directory: {}\n", cwd_path.join("/")));
             }
 
+            "make" if arg == Some("-p") => {
+                if let Some(path) = arg2 {
+                    let mut root = ROOT_DIR.lock();
+                    make_dirs(&mut term, resolve_cwd_mut(&mut root, &cwd_path), path);
+                } else { term.write_str("Usage: make -p <a/b/c>\n"); }
+            }
+
             "make" => {
                 if let Some(folder) = arg {
                     let mut root = ROOT_DIR.lock();
@@ -225,53 +1128,197 @@ pub async fn katalyst_repl() {
                 } else { term.write_str("Invalid spawn syntax. Use: spawn foldername\n"); }
             }
 
+            "touch" => {
+                if let Some(name) = arg {
+                    let mut root = ROOT_DIR.lock();
+                    touch_file(&mut term, resolve_cwd_mut(&mut root, &cwd_path), name);
+                } else { term.write_str("Usage: touch <file>\n"); }
+            }
+
+            "export" | "import" => {
+                if let (Some(a), Some(b)) = (arg, arg2) {
+                    let mut root = ROOT_DIR.lock();
+                    let cwd = resolve_cwd_mut(&mut root, &cwd_path);
+                    if command == "export" {
+                        export_dir(&mut term, cwd, a, b);
+                    } else {
+                        import_dir(&mut term, cwd, a, b);
+                    }
+                } else if command == "export" {
+                    term.write_str("Usage: export <folder> <file>\n");
+                } else {
+                    term.write_str("Usage: import <file> <folder>\n");
+                }
+            }
+
+            "lock" | "unlock" => {
+                if let Some(name) = arg {
+                    let mut root = ROOT_DIR.lock();
+                    set_locked(&mut term, resolve_cwd_mut(&mut root, &cwd_path), name, command == "lock");
+                } else { term.write_str(&format!("Usage: {} <file>\n", command)); }
+            }
+
             "del" => {
-                if let Some(folder) = arg {
+                let (recursive, target) = if arg == Some("-r") { (true, arg2) } else { (false, arg) };
+                if let Some(folder) = target {
                     let mut root = ROOT_DIR.lock();
                     let cwd = resolve_cwd_mut(&mut root, &cwd_path);
-                    despawn_file_folder(&mut term, cwd, folder);
+                    if let Some(entry) = despawn_file_folder(&mut term, cwd, folder, recursive) {
+                        trash_entry(&mut root, entry);
+                    }
                 } else { term.write_str("Invalid despawn syntax. Use: despawn foldername\n"); }
             }
 
-            "peek" => {
-                let root_ref = ROOT_DIR.lock();
-                scan_files(&mut term, &root_ref, &cwd_path, arg);
+            "undelete" => {
+                if let Some(name) = arg {
+                    let mut root = ROOT_DIR.lock();
+                    match take_from_trash(&mut root, name) {
+                        Some(entry) => {
+                            let cwd = resolve_cwd_mut(&mut root, &cwd_path);
+                            // Name taken here: leave it in the trash
+                            if let Err(entry) = restore_entry(&mut term, cwd, entry) {
+                                trash_entry(&mut root, entry);
+                            }
+                        }
+                        None => term.write_str(&format!("'{}' is not in the trash\n", name)),
+                    }
+                } else { term.write_str("Usage: undelete <name>\n"); }
             }
 
+            "trash" => list_trash(&mut term, &ROOT_DIR.lock()),
+            "empty-trash" => empty_trash(&mut term, &mut ROOT_DIR.lock()),
 
+            "mv" => {
+                if let (Some(src), Some(dst)) = (arg, arg2) {
+                    let mut root = ROOT_DIR.lock();
+                    let cwd = resolve_cwd_mut(&mut root, &cwd_path);
+                    move_file_folder(&mut term, cwd, src, dst);
+                } else { term.write_str("Usage: mv <source> <dest>\n"); }
+            }
 
+            "cp" => {
+                if let (Some(src), Some(dst)) = (arg, arg2) {
+                    let mut root = ROOT_DIR.lock();
+                    let cwd = resolve_cwd_mut(&mut root, &cwd_path);
+                    copy_file_folder(&mut term, cwd, src, dst);
+                } else { term.write_str("Usage: cp <source> <dest>\n"); }
+            }
 
+            "wc" => {
+                if let Some(name) = arg {
+                    let root = ROOT_DIR.lock();
+                    count_file(&mut term, resolve_cwd(&root, &cwd_path), name);
+                } else { term.write_str("Usage: wc <file>\n"); }
+            }
 
-            "->" => {
-                if let Some(target) = arg {
+            "head" | "tail" => match parse_head_args(input.split_whitespace().skip(1)) {
+                Some((Some(name), n)) => {
+                    // Render under the lock, page after releasing it
+                    let mut text = String::new();
+                    head_file(&mut text, resolve_cwd(&ROOT_DIR.lock(), &cwd_path), name, n, command == "tail");
+                    page_output(&mut term, &mut scancodes, &mut keyboard, &text).await;
+                }
+                _ => term.write_str(&format!("Usage: {} <file> [N]\n", command)),
+            },
+
+            "sort" => {
+                let (flags, name) = parse_sort_args(input.split_whitespace().skip(1));
+                match (flags, name) {
+                    (Some((reverse, unique, write)), Some(name)) => {
+                        let mut root = ROOT_DIR.lock();
+                        sort_file(&mut term, resolve_cwd_mut(&mut root, &cwd_path), name, reverse, unique, write);
+                    }
+                    _ => term.write_str("Usage: sort [-r] [-u] [-w] <file>\n"),
+                }
+            }
+
+            "stat" => {
+                if let Some(name) = arg {
                     let root = ROOT_DIR.lock();
-                    let mut temp = &*root;
-                    let mut path_stack = vec![temp.name];
-
-                    if target.starts_with('/') {
-                        // Absolute path
-                        let parts: Vec<&str> = target.split('/').filter(|s| !s.is_empty()).collect();
-                        let mut success = true;
-                        for part in parts.iter() {
-                            if let Some(child) = temp.subdirs.get(part) {
-                                temp = child;
-                                path_stack.push(child.name);
-                            } else {
-                                term.write_str(&format!("Directory '{}' not found\n", part));
-                                success = false;
-                                break;
-                            }
-                        }
-                        if success { cwd_path = path_stack; }
-                    } else {
-                        // Relative path
-                        for part in cwd_path.iter().skip(1) { temp = temp.subdirs.get(part).unwrap(); }
+                    stat_entry(&mut term, resolve_cwd(&root, &cwd_path), name);
+                } else { term.write_str("Usage: stat <name>\n"); }
+            }
+
+            "diff" => {
+                if let (Some(a), Some(b)) = (arg, arg2) {
+                    let root = ROOT_DIR.lock();
+                    diff_files(&mut term, resolve_cwd(&root, &cwd_path), a, b);
+                } else { term.write_str("Usage: diff <file> <file>\n"); }
+            }
 
-                        if let Some(child) = temp.subdirs.get(target) {
-                            cwd_path.push(child.name);
+            "fsck" => match arg {
+                None | Some("-f") => fsck(&mut term, &mut ROOT_DIR.lock(), arg.is_some()),
+                Some(_) => term.write_str("Usage: fsck [-f]\n"),
+            },
+
+            "du" => {
+                let root = ROOT_DIR.lock();
+                disk_usage(&mut term, resolve_cwd(&root, &cwd_path), arg);
+            }
+
+            "find" => {
+                if let Some(pattern) = arg {
+                    let root = ROOT_DIR.lock();
+                    find_files(&mut term, resolve_cwd(&root, &cwd_path), pattern);
+                } else { term.write_str("Usage: find <pattern>\n"); }
+            }
+
+            "seek" => {
+                let (pattern, ignore_case, recursive) = parse_seek_args(input.split_whitespace().skip(1));
+                if let Some(pattern) = pattern {
+                    let root = ROOT_DIR.lock();
+                    seek_in_cwd(&mut term, resolve_cwd(&root, &cwd_path), pattern, ignore_case, recursive);
+                } else { term.write_str("Usage: seek [-i] [-r] <pattern>\n"); }
+            }
+
+            "peek" => {
+                let args: Vec<&str> = input.split_whitespace().skip(1).collect();
+                let long = args.contains(&"-l");
+                let hex = args.contains(&"-x");
+                let no_pager = args.contains(&"--no-pager");
+                let target = args.iter().copied().find(|a| !a.starts_with('-'));
+
+                // Render under the lock, page after releasing it
+                let file_text = {
+                    let root_ref = ROOT_DIR.lock();
+                    let cwd = resolve_cwd(&root_ref, &cwd_path);
+                    let file_spec = target.filter(|a| {
+                        let name = a.split(':').next().unwrap_or("");
+                        a.contains(':') || cwd.get_file(name).is_some()
+                    });
+                    if let Some(spec) = file_spec {
+                        let mut text = String::new();
+                        if hex {
+                            hexdump_file(&mut text, cwd, spec);
                         } else {
-                            term.write_str(&format!("Directory '{}' not found\n", target));
+                            render_file(&mut text, cwd, spec);
                         }
+                        Some(text)
+                    } else if hex {
+                        term.write_str("Usage: peek -x <file>[:start-end]\n");
+                        None
+                    } else {
+                        scan_files(&mut term, &root_ref, &cwd_path, target, long);
+                        None
+                    }
+                };
+                if let Some(text) = file_text {
+                    if no_pager {
+                        term.write_str(&text);
+                    } else {
+                        page_output(&mut term, &mut scancodes, &mut keyboard, &text).await;
+                    }
+                }
+            }
+
+            "->" if arg == Some("-") => swap_to_previous(&mut term, &mut cwd_path, &mut prev_cwd_path),
+            "back" => swap_to_previous(&mut term, &mut cwd_path, &mut prev_cwd_path),
+
+            "->" => {
+                if let Some(target) = arg {
+                    let root = ROOT_DIR.lock();
+                    if let Some(path) = resolve_path(&mut term, &root, &cwd_path, target) {
+                        prev_cwd_path = Some(core::mem::replace(&mut cwd_path, path));
                     }
                 } else {
                     term.write_str("Usage: -> <dir>\n");
@@ -280,6 +1327,7 @@ pub async fn katalyst_repl() {
 
             "<-" => {
                 if cwd_path.len() > 1 {
+                    prev_cwd_path = Some(cwd_path.clone());
                     cwd_path.pop();
                 } else {
                     term.write_str("Already at root\n");
@@ -292,24 +1340,715 @@ pub async fn katalyst_repl() {
     }
 }
 
-fn resolve_cwd<'a>(root: &'a Directory, cwd_path: &[&'static str]) -> &'a Directory {
+/// Destination for command output: the screen, or a buffer feeding a pipe
+pub trait Output {
+    fn write_str(&mut self, s: &str);
+
+    fn write_char(&mut self, c: char) {
+        self.write_str(c.encode_utf8(&mut [0; 4]));
+    }
+}
+
+impl Output for Terminal {
+    fn write_str(&mut self, s: &str) {
+        Terminal::write_str(self, s);
+    }
+
+    fn write_char(&mut self, c: char) {
+        Terminal::write_char(self, c);
+    }
+}
+
+impl Output for String {
+    fn write_str(&mut self, s: &str) {
+        self.push_str(s);
+    }
+}
+
+/// Create the task for a `BACKGROUND_TASKS` name
+fn background_task(name: &str) -> Option<Task> {
+    match name {
+        "clock" => Some(Task::new(clock_task())),
+        _ => None,
+    }
+}
+
+/// Show the RTC time in the top-right corner, redrawn every second.
+///
+/// Writes straight to video memory without touching the `Terminal` or the
+/// hardware cursor. `spawn` reserves row 0 as a status line first, so the
+/// terminal never writes, scrolls or shows scrollback there.
+async fn clock_task() {
+    let color = ColorCode::new(Color::Black, Color::LightGray);
+    loop {
+        let now = crate::rtc::read_rtc();
+        let text = format!("{:02}:{:02}:{:02}", now.hour, now.minute, now.second);
+        let start = WIDTH - text.len();
+        for (i, b) in text.bytes().enumerate() {
+            let offset = 2 * (start + i);
+            unsafe {
+                VGA_BUFFER.add(offset).write_volatile(b);
+                VGA_BUFFER.add(offset + 1).write_volatile(color.as_u8());
+            }
+        }
+        sleep_ms(1000).await;
+    }
+}
+
+/// Parse an `at` delay such as `+30s`, `+5m` or `+1h` into seconds.
+///
+/// The leading `+` and the unit are optional; a bare number is seconds.
+fn parse_delay(arg: &str) -> Option<u64> {
+    let arg = arg.strip_prefix('+').unwrap_or(arg);
+    let (num, scale) = match arg.as_bytes().last()? {
+        b's' => (&arg[..arg.len() - 1], 1),
+        b'm' => (&arg[..arg.len() - 1], 60),
+        b'h' => (&arg[..arg.len() - 1], 3600),
+        _ => (arg, 1),
+    };
+    num.parse::<u64>().ok().filter(|n| *n > 0)?.checked_mul(scale)
+}
+
+/// Live view of uptime, CPU usage and tasks, refreshed every second until a key is pressed.
+///
+/// Waits on the keyboard and the timer together, so other tasks keep running
+/// meanwhile. Scancodes go through `keyboard`, so multi-byte keys are
+/// consumed whole.
+async fn run_monitor(
+    term: &mut Terminal,
+    scancodes: &mut ScancodeStream,
+    keyboard: &mut Keyboard<AnyLayout, ScancodeSet1>,
+) {
+    use crate::sys::{get_cpu_usage, get_uptime, ticks_per_second, IDLE_TICKS, UPTIME_TICKS};
+    use core::sync::atomic::Ordering;
+
+    loop {
+        let (hours, mins, secs) = get_uptime();
+        term.clear_screen();
+        term.write_str("=== Monitor (press any key to exit) ===\n");
+        term.write_str(&format!("Uptime: {:02}:{:02}:{:02}\n", hours, mins, secs));
+        term.write_str(&format!("CPU Usage: {}%\n", get_cpu_usage()));
+        term.write_str(&format!("Idle ticks: {}\n", IDLE_TICKS.load(Ordering::Relaxed)));
+        {
+            let exec = crate::task::executor::EXECUTOR.lock();
+            term.write_str(&format!("Tasks ({}):\n", exec.task_count()));
+            for id in exec.task_ids() {
+                if exec.current_task() == Some(*id) {
+                    term.write_str(&format!("- Task ID: {} (running)\n", id));
+                } else {
+                    term.write_str(&format!("- Task ID: {}\n", id));
+                }
+            }
+        }
+
+        let next_refresh = UPTIME_TICKS.load(Ordering::Relaxed) + ticks_per_second();
+        loop {
+            match select(scancodes.next(), sleep_until(next_refresh)).await {
+                Either::Left((Some(scancode), _)) => {
+                    if let Ok(Some(event)) = keyboard.add_byte(scancode) {
+                        if event.state == KeyState::Down {
+                            term.reset_screen();
+                            return;
+                        }
+                    }
+                }
+                Either::Left((None, _)) => {
+                    term.reset_screen();
+                    return;
+                }
+                Either::Right(_) => break,
+            }
+        }
+    }
+}
+
+/// Show `text` a screenful at a time.
+///
+/// Space shows the next page, Enter the next line and `q` stops. Output that
+/// fits on one screen is written straight through.
+async fn page_output(
+    term: &mut Terminal,
+    scancodes: &mut ScancodeStream,
+    keyboard: &mut Keyboard<AnyLayout, ScancodeSet1>,
+    text: &str,
+) {
+    // Split into screen rows so wrapped lines count against the page
+    let mut rows: Vec<&str> = Vec::new();
+    for line in text.lines() {
+        let mut rest = line;
+        while let Some((split, _)) = rest.char_indices().nth(WIDTH) {
+            rows.push(&rest[..split]);
+            rest = &rest[split..];
+        }
+        rows.push(rest);
+    }
+
+    let page = term.rows() - 1;
+    if rows.len() <= page {
+        term.write_str(text);
+        return;
+    }
+
+    let mut shown = 0;
+    let mut step = page;
+    loop {
+        for row in rows.iter().skip(shown).take(step) {
+            term.write_str(row);
+            // A full-width row already wrapped the cursor
+            if row.chars().count() < WIDTH {
+                term.write_char('\n');
+            }
+        }
+        shown = (shown + step).min(rows.len());
+        if shown == rows.len() {
+            return;
+        }
+
+        term.write_colored_str(PAGER_PROMPT, term.theme.prompt);
+        step = loop {
+            let scancode = match scancodes.next().await {
+                Some(scancode) => scancode,
+                None => continue,
+            };
+            let key = match keyboard.add_byte(scancode) {
+                Ok(Some(event)) => keyboard.process_keyevent(event),
+                _ => None,
+            };
+            match key {
+                Some(DecodedKey::Unicode(' ')) => break page,
+                Some(DecodedKey::Unicode('\n' | '\r')) => break 1,
+                Some(DecodedKey::Unicode('q')) => break 0,
+                _ => {}
+            }
+        };
+        term.clear_line();
+        if step == 0 {
+            return;
+        }
+    }
+}
+
+/// Detailed usage for `man`, or `None` for undocumented commands
+fn man_page(command: &str) -> Option<&'static str> {
+    let page = match command {
+        "help" => "help\n  Print a short overview of all commands grouped by topic.",
+        "man" => "man [command]\n  Print usage, options and examples for a command.\n  Without an argument, list every documented command.\n  Example: man seek",
+        "wipe" | "wp" => "wipe, wp\n  Clear the screen. Scrollback is kept.",
+        "halt" => "halt\n  Stop the CPU. Nothing runs until the machine is reset.",
+        "shutdown" => "shutdown\n  Power off through ACPI (works under QEMU).",
+        "reboot" => "reboot\n  Reset the machine by pulsing the keyboard controller reset line.",
+        "spark" => "spark\n  Print a test message.",
+        "core" => "core\n  Report uptime, CPU usage, temperature, heap usage and running tasks.",
+        "monitor" => "monitor\n  Live view of uptime, CPU usage and tasks, refreshed every second.\n  Press any key to leave.",
+        "date" => "date\n  Print the date and time from the real-time clock.",
+        "info" | "version" => "info\n  Show the kernel version, build time and profile, timer rate, heap size,\n  built-in features and uptime. 'version' is the same command.",
+        "meminfo" => "meminfo\n  List the memory regions reported by the bootloader (start, end, size\n  and type), then usable physical memory, allocated and free 4 KiB\n  frames, and heap usage.",
+        "spawn" => "spawn task\n  Start a background task. Available: clock (time in the top-right\n  corner, redrawn every second). The clock keeps the top row to itself\n  until it is stopped with 'kill'.",
+        "at" => "at +delay command\n  Run a command once after a delay, in the folder that is current when\n  it fires. The delay is a number followed by s, m or h (seconds if no\n  unit). $VARs are filled in when 'at' runs. Input being typed when the\n  command fires is kept.\n  Example: at +30s fsck, at +5m touch notes.txt",
+        "scheduled" => "scheduled\n  List commands queued with 'at', soonest first, with their job ids.",
+        "cancel" => "cancel job_id\n  Remove a command queued with 'at' before it runs.",
+        "jobs" => "jobs\n  List background tasks started with 'spawn' and their task ids.",
+        "kill" => "kill task_id\n  Cancel a running task. The shell's own task can't be killed.\n  Task ids are listed by 'core' and 'monitor'.",
+        "theme" => "theme [name]\n  Switch the color theme. Without a name, list the available themes.\n  It lasts until reboot.\n  Example: theme amber",
+        "layout" => "layout [name]\n  Switch the keyboard layout. Without a name, show the current one and\n  list the others: us, uk, de, azerty, colemak, dvorak, dvp (Dvorak\n  programmer), jis. The choice lasts until reboot.\n  Example: layout uk",
+        "here" => "here\n  Print the current directory path.",
+        "make" => "make [-p] name\n  Create a file (name contains '.') or a folder (no '.').\n  -p  create a folder path like a/b/c, reusing folders that exist\n  Example: make notes.txt, make projects, make -p src/fs/tests",
+        "touch" => "touch file\n  Create an empty file, or update the modified time of an existing one.",
+        "del" => "del [-r] name\n  Move a file or an empty folder to the trash.\n  -r  also delete non-empty folders and everything below them\n  See 'undelete', 'trash' and 'empty-trash'.",
+        "undelete" => "undelete name\n  Restore the most recently deleted entry called name into the current\n  folder.",
+        "trash" => "trash\n  List deleted entries, oldest first, with their slot numbers and sizes.",
+        "empty-trash" => "empty-trash\n  Permanently delete everything in the trash.",
+        "lock" => "lock file\n  Protect a file: del, mv and touch refuse to change it, and folders\n  holding locked files can't be deleted. Undo with 'unlock'.",
+        "unlock" => "unlock file\n  Remove the protection set by 'lock'.",
+        "export" => "export folder file\n  Bundle a folder and everything below it into one file.\n  Example: export projects projects.bundle",
+        "import" => "import file folder\n  Recreate a bundle made by 'export' as a new folder.\n  Example: import projects.bundle restored",
+        "mv" => "mv source dest\n  Rename a file or folder, or move it into an existing folder dest.",
+        "cp" => "cp source dest\n  Copy a file, or a folder with everything below it.",
+        "peek" => "peek [-l] [-x] [--no-pager] [folder|file[:N-M]]\n  List a folder, or print a file.\n  -l          one entry per line with created/modified times\n  -x          hex and ASCII dump; file:start-end limits it to those bytes\n              (end exclusive, decimal or 0x hex)\n  --no-pager  print long files at once instead of a screen at a time\n  file:N-M    print only lines N to M (file:N for one line)\n  In the pager: space = next page, enter = next line, q = quit.",
+        "->" => "-> path\n  Change directory. '/' starts from root, '..' goes up, '.' stays.\n  -> -  return to the previous directory\n  Example: -> /projects, -> ../docs",
+        "<-" => "<-\n  Go up one directory.",
+        "back" => "back\n  Return to the previous directory (same as '-> -').",
+        "wc" => "wc file\n  Print the line, word and byte counts of a file.",
+        "head" => "head file [N]\n  Print the first N lines of a text file (10 by default), a screen at a\n  time. Works in a pipe: peek log.txt | seek error | head 3",
+        "tail" => "tail file [N]\n  Print the last N lines of a text file (10 by default), a screen at a\n  time. Works in a pipe: peek log.txt | tail 5",
+        "sort" => "sort [-r] [-u] [-w] file\n  Print the lines of a text file in sorted order.\n  -r  reverse the order\n  -u  drop duplicate lines\n  -w  write the result back to the file instead of printing it\n  Example: sort -u -w names.txt",
+        "stat" => "stat name\n  Show details of a file (size, lines, UTF-8 validity, created and\n  modified times since boot) or a folder (entry counts and total size).",
+        "diff" => "diff a b\n  Compare two text files line by line. Lines only in a are shown in red\n  with '-', lines only in b in green with '+', each with its line number.\n  Example: diff config.txt config.bak",
+        "fsck" => "fsck [-f]\n  Check the whole file system: entry names match their keys, files have\n  a '.' and folders don't, no file is over 64 KiB, and no file was\n  modified before it was created. Ends with a summary.\n  -f  reset mismatched names to their keys and fix bad timestamps",
+        "du" => "du [folder]\n  Show the total size of each subfolder, largest first, and a grand total.\n  Large files are flagged. Hidden entries such as .trash are not counted.",
+        "find" => "find pattern\n  List files and folders below the current folder whose names contain pattern.\n  Hidden entries such as .trash are skipped.",
+        "seek" => "seek [-i] [-r] pattern\n  Print the first matching line of each file in the current folder.\n  -i  ignore case\n  -r  also search subfolders\n  Patterns are literal except: ^ anchors at line start, $ at line end,\n  and . matches any character.\n  Example: seek -r ^todo",
+        "history" => "history [clear]\n  List the last 20 commands with their numbers, or forget all of them.\n  !!  re-run the last command\n  !N  re-run command number N\n  Anything after !! or !N is appended. Up to 500 commands are kept.\n  Example: !3 | wc",
+        "alias" => "alias [name=expansion]\n  Define a shortcut for a command. Without arguments, list aliases.\n  Aliases last until reboot.\n  Example: alias ll=peek -l",
+        "unalias" => "unalias name\n  Remove an alias.",
+        "set" => "set NAME value\n  Set a variable. $NAME in later commands is replaced by its value.\n  Names use letters, digits and '_'. Variables last\n  until reboot.\n  Example: set NOTES /notes, then -> $NOTES",
+        "unset" => "unset NAME\n  Remove a variable.",
+        "env" => "env\n  List all variables. CWD (current directory, as /path for '->') and\n  UPTIME are built in and read-only. Unset variables expand to nothing, with a warning.",
+        "bind" => "bind [key [text]]\n  Make a function key (F1-F12) type text at the cursor. Quote the text\n  to keep leading or trailing spaces. Without text, show the key's\n  binding; without arguments, list all. Bindings last until reboot.\n  By default F1 types 'help', F2 '-> ' and F3 'peek -l'.\n  Example: bind F4 \"peek -l \"",
+        "prompt" => "prompt [template|reset]\n  Change the prompt. In the template %p is the current path, %t the time,\n  %u the uptime and %% a '%'. Quote it to keep a trailing space. Without\n  arguments, show the template; 'reset' restores the default. The\n  template lasts until reboot.\n  Example: prompt \"[%t] %p> \"",
+        "clip" => "clip [text]\n  Put text on the clipboard, or show the clipboard without arguments.\n  At the end of a pipe, copies the piped lines: peek notes.txt | clip\n  Ctrl+V types the clipboard's first line at the cursor.",
+        "paste" => "paste\n  Start the next prompt with the clipboard's first line, ready to edit.",
+        "unbind" => "unbind key\n  Remove a function-key binding.",
+        "pipe" => "cmd | cmd [| cmd ...]\n  Feed the text output of one command into the next.\n  peek file, seek, sort, head, tail and wc can be chained. Given piped text,\n  peek passes it through, seek prints the matching lines, sort sorts them,\n  head and tail keep the first or last lines and wc counts them. clip\n  copies them to the clipboard.\n  Example: peek notes.txt | seek -i todo | wc",
+        _ => return None,
+    };
+    Some(page)
+}
+
+/// Split `head` / `tail` arguments into the file name and the line count.
+///
+/// A bare number is the count; `None` if more than one name or count is given.
+fn parse_head_args<'a>(args: impl Iterator<Item = &'a str>) -> Option<(Option<&'a str>, usize)> {
+    let mut name = None;
+    let mut count = None;
+    for arg in args {
+        match arg.parse::<usize>() {
+            Ok(n) if count.is_none() => count = Some(n),
+            Err(_) if name.is_none() => name = Some(arg),
+            _ => return None,
+        }
+    }
+    Some((name, count.unwrap_or(HEAD_DEFAULT_LINES)))
+}
+
+/// Split `sort` arguments into the `(-r, -u, -w)` flags and the file name.
+///
+/// The flags are `None` if an unknown flag was given.
+fn parse_sort_args<'a>(args: impl Iterator<Item = &'a str>) -> (Option<(bool, bool, bool)>, Option<&'a str>) {
+    let (mut reverse, mut unique, mut write) = (false, false, false);
+    let mut name = None;
+    for arg in args {
+        match arg {
+            "-r" => reverse = true,
+            "-u" => unique = true,
+            "-w" => write = true,
+            _ if arg.starts_with('-') => return (None, None),
+            _ => name = Some(arg),
+        }
+    }
+    (Some((reverse, unique, write)), name)
+}
+
+/// Split `seek` arguments into the pattern and the `-i` / `-r` flags
+fn parse_seek_args<'a>(args: impl Iterator<Item = &'a str>) -> (Option<&'a str>, bool, bool) {
+    let mut pattern = None;
+    let mut ignore_case = false;
+    let mut recursive = false;
+    for a in args {
+        match a {
+            "-i" => ignore_case = true,
+            "-r" => recursive = true,
+            _ if pattern.is_none() => pattern = Some(a),
+            _ => {}
+        }
+    }
+    (pattern, ignore_case, recursive)
+}
+
+/// Run a `|`-separated pipeline, feeding each stage's output to the next.
+///
+/// Returns the last stage's output, or the message to print when a stage
+/// can't run.
+fn run_pipeline(root: &Directory, cwd_path: &[&'static str], input: &str) -> Result<String, String> {
+    let cwd = resolve_cwd(root, cwd_path);
+    let mut piped: Option<String> = None;
+    for stage in input.split('|') {
+        let mut out = String::new();
+        let mut parts = stage.split_whitespace();
+        match (parts.next(), piped.as_deref()) {
+            (Some("peek"), piped) => {
+                let args: Vec<&str> = parts.collect();
+                let hex = args.contains(&"-x");
+                match (args.iter().copied().find(|a| !a.starts_with('-')), piped) {
+                    (Some(spec), _) if hex => hexdump_file(&mut out, cwd, spec),
+                    (Some(spec), _) => render_file(&mut out, cwd, spec),
+                    (None, Some(text)) => out.push_str(text),
+                    (None, None) => return Err("Usage: peek <file> | ...\n".into()),
+                }
+            }
+            (Some("seek"), piped) => match (parse_seek_args(parts), piped) {
+                ((Some(pattern), ignore_case, _), Some(text)) => seek_in_text(&mut out, text, pattern, ignore_case),
+                ((Some(pattern), ignore_case, recursive), None) => {
+                    seek_in_cwd(&mut out, cwd, pattern, ignore_case, recursive)
+                }
+                ((None, _, _), _) => return Err("Usage: ... | seek [-i] <pattern>\n".into()),
+            },
+            (Some("wc"), piped) => match (parts.next(), piped) {
+                (Some(name), _) => count_file(&mut out, cwd, name),
+                (None, Some(text)) => count_text(&mut out, text),
+                (None, None) => return Err("Usage: ... | wc\n".into()),
+            },
+            (Some(cmd @ ("head" | "tail")), piped) => match (parse_head_args(parts), piped) {
+                (Some((Some(name), n)), _) => head_file(&mut out, cwd, name, n, cmd == "tail"),
+                (Some((None, n)), Some(text)) => head_text(&mut out, text, n, cmd == "tail"),
+                _ => return Err(format!("Usage: ... | {} [N]\n", cmd)),
+            },
+            (Some("clip"), Some(text)) => {
+                let count = crate::clipboard::set(text);
+                out.push_str(&format!("Copied {} lines to the clipboard\n", count));
+            }
+            (Some("sort"), piped) => match (parse_sort_args(parts), piped) {
+                ((Some((reverse, unique, false)), None), Some(text)) => sort_text(&mut out, text, reverse, unique),
+                ((Some((reverse, unique, false)), Some(name)), None) => match cwd.get_file(name).map(|f| core::str::from_utf8(f.read())) {
+                    Some(Ok(text)) => sort_text(&mut out, text, reverse, unique),
+                    Some(Err(_)) => return Err(format!("'{}' is not a text file\n", name)),
+                    None => return Err(format!("'{}' not found\n", name)),
+                },
+                _ => return Err("Usage: ... | sort [-r] [-u]\n".into()),
+            },
+            (Some(cmd), _) => return Err(format!("'{}' can't be used in a pipe\n", cmd)),
+            (None, _) => return Err("Empty command in pipe\n".into()),
+        }
+        piped = Some(out);
+    }
+    Ok(piped.unwrap_or_default())
+}
+
+/// Replace a leading alias with its expansion, following chained aliases.
+///
+/// Returns `None` if expansion doesn't settle within `ALIAS_DEPTH_LIMIT` steps.
+fn expand_alias(aliases: &BTreeMap<String, String>, input: &str) -> Option<String> {
+    let mut line = input.to_string();
+    for _ in 0..ALIAS_DEPTH_LIMIT {
+        let (first, rest) = line.split_once(' ').unwrap_or((line.as_str(), ""));
+        match aliases.get(first) {
+            Some(expansion) if rest.is_empty() => line = expansion.clone(),
+            Some(expansion) => line = format!("{} {}", expansion, rest),
+            None => return Some(line),
+        }
+    }
+    None
+}
+
+/// The `pc_keyboard` layout for a `LAYOUTS` name
+fn layout_by_name(name: &str) -> Option<AnyLayout> {
+    let layout = match name {
+        "us" => AnyLayout::Us104Key(layouts::Us104Key),
+        "uk" => AnyLayout::Uk105Key(layouts::Uk105Key),
+        "de" => AnyLayout::De105Key(layouts::De105Key),
+        "azerty" => AnyLayout::Azerty(layouts::Azerty),
+        "colemak" => AnyLayout::Colemak(layouts::Colemak),
+        "dvorak" => AnyLayout::Dvorak104Key(layouts::Dvorak104Key),
+        "dvp" => AnyLayout::DVP104Key(layouts::DVP104Key),
+        "jis" => AnyLayout::Jis109Key(layouts::Jis109Key),
+        _ => return None,
+    };
+    Some(layout)
+}
+
+fn new_keyboard(layout: AnyLayout) -> Keyboard<AnyLayout, ScancodeSet1> {
+    Keyboard::new(ScancodeSet1::new(), layout, HandleControl::Ignore)
+}
+
+/// Fill in a prompt template: %p is the path, %t the time, %u the uptime
+/// and %% a literal '%'. Other characters are kept as they are.
+fn render_prompt(template: &str, cwd_path: &[&str]) -> String {
+    let mut prompt = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            prompt.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('p') => prompt.push_str(&cwd_path.join("/")),
+            Some('t') => {
+                let now = crate::rtc::read_rtc();
+                prompt.push_str(&format!("{:02}:{:02}:{:02}", now.hour, now.minute, now.second));
+            }
+            Some('u') => {
+                let (h, m, s) = crate::sys::get_uptime();
+                prompt.push_str(&format!("{:02}:{:02}:{:02}", h, m, s));
+            }
+            Some('%') => prompt.push('%'),
+            Some(other) => {
+                prompt.push('%');
+                prompt.push(other);
+            }
+            None => prompt.push('%'),
+        }
+    }
+    prompt
+}
+
+/// Can `name` be used as a variable name?
+fn is_var_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Value of a variable, computing the built-in ones from the shell state
+fn var_value(vars: &BTreeMap<String, String>, cwd_path: &[&str], name: &str) -> Option<String> {
+    match name {
+        // Rooted form that `->` accepts, e.g. /projects for main/projects
+        "CWD" => Some(format!("/{}", cwd_path[1..].join("/"))),
+        "UPTIME" => {
+            let (hours, mins, secs) = crate::sys::get_uptime();
+            Some(format!("{:02}:{:02}:{:02}", hours, mins, secs))
+        }
+        _ => vars.get(name).cloned(),
+    }
+}
+
+/// Replace each `$NAME` in `input` with the variable's value.
+///
+/// Unset variables expand to nothing and print a warning. A `$` that isn't
+/// followed by a name is kept as is.
+fn expand_vars(term: &mut Terminal, vars: &BTreeMap<String, String>, cwd_path: &[&str], input: &str) -> String {
+    let mut line = String::new();
+    let mut rest = input;
+    while let Some(pos) = rest.find('$') {
+        line.push_str(&rest[..pos]);
+        let after = &rest[pos + 1..];
+        let len = after
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        let name = &after[..len];
+        if name.is_empty() {
+            line.push('$');
+        } else {
+            match var_value(vars, cwd_path, name) {
+                Some(value) => line.push_str(&value),
+                None => term.write_str(&format!("Warning: ${} is not set\n", name)),
+            }
+        }
+        rest = &after[len..];
+    }
+    line.push_str(rest);
+    line
+}
+
+/// Name of a function key, as used by `bind`
+fn fkey_name(code: KeyCode) -> Option<&'static str> {
+    let name = match code {
+        KeyCode::F1 => "F1",
+        KeyCode::F2 => "F2",
+        KeyCode::F3 => "F3",
+        KeyCode::F4 => "F4",
+        KeyCode::F5 => "F5",
+        KeyCode::F6 => "F6",
+        KeyCode::F7 => "F7",
+        KeyCode::F8 => "F8",
+        KeyCode::F9 => "F9",
+        KeyCode::F10 => "F10",
+        KeyCode::F11 => "F11",
+        KeyCode::F12 => "F12",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Bindings in place at boot, all real REPL commands
+fn default_bindings() -> BTreeMap<String, String> {
+    let mut bindings = BTreeMap::new();
+    bindings.insert("F1".to_string(), "help".to_string());
+    bindings.insert("F2".to_string(), "-> ".to_string());
+    bindings.insert("F3".to_string(), "peek -l".to_string());
+    bindings
+}
+
+/// Switch to the previous directory, like `cd -`
+fn swap_to_previous(
+    term: &mut Terminal,
+    cwd_path: &mut Vec<&'static str>,
+    prev_cwd_path: &mut Option<Vec<&'static str>>,
+) {
+    match prev_cwd_path.as_mut() {
+        Some(prev) => {
+            core::mem::swap(cwd_path, prev);
+            term.write_str(&format!("{}\n", cwd_path.join("/")));
+        }
+        None => term.write_str("No previous directory\n"),
+    }
+}
+
+/// Resolve a folder path to the component list used for `cwd_path`.
+///
+/// A leading `/` starts from root, anything else from `cwd_path`. `.` is
+/// skipped and `..` goes up one level. Prints the reason and returns `None`
+/// if a component is missing or empty, or `..` would leave root.
+fn resolve_path(
+    term: &mut Terminal,
+    root: &Directory,
+    cwd_path: &[&'static str],
+    input: &str,
+) -> Option<Vec<&'static str>> {
+    let (mut path, rest) = match input.strip_prefix('/') {
+        Some(rest) => (vec![root.name], rest),
+        None => (cwd_path.to_vec(), input),
+    };
+
+    let rest = rest.strip_suffix('/').unwrap_or(rest);
+    if rest.is_empty() {
+        return Some(path);
+    }
+
+    for part in rest.split('/') {
+        match part {
+            "" => {
+                term.write_str(&format!("Invalid path '{}'\n", input));
+                return None;
+            }
+            "." => {}
+            ".." => {
+                if path.len() > 1 {
+                    path.pop();
+                } else {
+                    term.write_str("Already at root\n");
+                    return None;
+                }
+            }
+            _ => match resolve_cwd(root, &path).subdirs.get(part) {
+                Some(child) => path.push(child.name),
+                None => {
+                    term.write_str(&format!("Directory '{}' not found\n", part));
+                    return None;
+                }
+            },
+        }
+    }
+    Some(path)
+}
+
+/// Walk `cwd_path` from root, or `None` if a folder on it no longer exists
+fn find_cwd<'a>(root: &'a Directory, cwd_path: &[&'static str]) -> Option<&'a Directory> {
     let mut temp = root;
     for part in cwd_path.iter().skip(1) {
-        temp = temp.subdirs.get(part).unwrap();
+        temp = temp.subdirs.get(part)?;
     }
-    temp
+    Some(temp)
+}
+
+/// The directory at `cwd_path`, falling back to root if it no longer exists
+fn resolve_cwd<'a>(root: &'a Directory, cwd_path: &[&'static str]) -> &'a Directory {
+    find_cwd(root, cwd_path).unwrap_or(root)
 }
 
+/// Mutable `resolve_cwd`, with the same fallback to root
 fn resolve_cwd_mut<'a>(root: &'a mut Directory, cwd_path: &[&'static str]) -> &'a mut Directory {
+    if find_cwd(root, cwd_path).is_none() {
+        return root;
+    }
     let mut temp = root;
     for part in cwd_path.iter().skip(1) {
-        temp = temp.subdirs.get_mut(part).unwrap();
+        temp = temp.subdirs.get_mut(part).expect("path checked by find_cwd");
     }
     temp
 }
 
 
-fn update_prompt(term: &mut Terminal, cwd_path: &[&str]) {
-    term.prompt = format!("katalyst@{}=> ", cwd_path.join("/"));
+fn update_prompt(term: &mut Terminal, template: &str, cwd_path: &[&str]) {
+    term.prompt = render_prompt(template, cwd_path);
     term.redraw_input(); // redraws prompt + current input
 }
+
+#[test_case]
+fn test_parse_delay() {
+    assert_eq!(parse_delay("+30s"), Some(30));
+    assert_eq!(parse_delay("+5m"), Some(300));
+    assert_eq!(parse_delay("1h"), Some(3600));
+    assert_eq!(parse_delay("45"), Some(45));
+    assert_eq!(parse_delay("+0s"), None);
+    assert_eq!(parse_delay("+s"), None);
+    assert_eq!(parse_delay(""), None);
+    assert_eq!(parse_delay("+5x"), None);
+    assert_eq!(parse_delay("+18446744073709551615h"), None);
+}
+
+#[test_case]
+fn test_history_expand() {
+    let mut term = Terminal::new("");
+    assert_eq!(term.history_expand("!!"), Err("!!: no such history entry\n".to_string()));
+
+    term.history = vec!["peek".to_string(), "-> /projects".to_string()];
+    assert_eq!(term.history_expand("!!"), Ok("-> /projects".to_string()));
+    assert_eq!(term.history_expand("!1"), Ok("peek".to_string()));
+    assert_eq!(term.history_expand("!1 -l"), Ok("peek -l".to_string()));
+    assert_eq!(term.history_expand("!3"), Err("!3: no such history entry\n".to_string()));
+    assert_eq!(term.history_expand("!0"), Err("!0: no such history entry\n".to_string()));
+    assert_eq!(term.history_expand("!"), Err("!: no such history entry\n".to_string()));
+    assert_eq!(term.history_expand("peek"), Ok("peek".to_string()));
+}
+
+#[test_case]
+fn test_expand_alias() {
+    let mut aliases = BTreeMap::new();
+    aliases.insert("l".to_string(), "ll".to_string());
+    aliases.insert("ll".to_string(), "peek -l".to_string());
+    assert_eq!(expand_alias(&aliases, "l"), Some("peek -l".to_string()));
+    assert_eq!(expand_alias(&aliases, "l docs"), Some("peek -l docs".to_string()));
+    assert_eq!(expand_alias(&aliases, "help"), Some("help".to_string()));
+
+    // Aliases that expand to themselves never settle
+    aliases.insert("a".to_string(), "b".to_string());
+    aliases.insert("b".to_string(), "a".to_string());
+    assert_eq!(expand_alias(&aliases, "a"), None);
+}
+
+#[test_case]
+fn test_expand_vars() {
+    let mut term = Terminal::new("");
+    let mut vars = BTreeMap::new();
+    vars.insert("NOTES".to_string(), "/notes".to_string());
+    let cwd_path = ["main", "projects"];
+
+    assert_eq!(expand_vars(&mut term, &vars, &cwd_path, "-> $NOTES"), "-> /notes");
+    assert_eq!(expand_vars(&mut term, &vars, &cwd_path, "echo $CWD/a"), "echo /projects/a");
+    assert_eq!(expand_vars(&mut term, &vars, &["main"], "$CWD"), "/");
+    assert_eq!(expand_vars(&mut term, &vars, &cwd_path, "a $UNSET b"), "a  b");
+    assert_eq!(expand_vars(&mut term, &vars, &cwd_path, "cost $ 5"), "cost $ 5");
+    assert_eq!(expand_vars(&mut term, &vars, &cwd_path, "$NOTES$NOTES"), "/notes/notes");
+}
+
+#[test_case]
+fn test_render_prompt() {
+    let cwd_path = ["main", "projects"];
+    assert_eq!(render_prompt("%p> ", &cwd_path), "main/projects> ");
+    assert_eq!(render_prompt("100%% ", &cwd_path), "100% ");
+    assert_eq!(render_prompt("%x", &cwd_path), "%x");
+    assert_eq!(render_prompt("end%", &cwd_path), "end%");
+    assert_eq!(render_prompt("%u", &cwd_path).len(), "00:00:00".len());
+}
+
+/// `main` holding `projects/app` and a `notes.txt`, for tests
+#[cfg(test)]
+fn test_tree() -> Directory {
+    let mut app = Directory::new("app");
+    app.add_file(crate::fs::file::File::new("main.rs"));
+    let mut projects = Directory::new("projects");
+    projects.add_subdir(app);
+    let mut notes = crate::fs::file::File::new("notes.txt");
+    notes.overwrite(b"pear\napple\nfig\napple\n");
+    let mut root = Directory::new("main");
+    root.add_subdir(projects);
+    root.add_file(notes);
+    root
+}
+
+#[test_case]
+fn test_resolve_path() {
+    let mut term = Terminal::new("");
+    let root = test_tree();
+    let cwd = ["main", "projects", "app"];
+    let resolve = |term: &mut Terminal, input| resolve_path(term, &root, &cwd, input);
+
+    assert_eq!(resolve(&mut term, "/"), Some(vec!["main"]));
+    assert_eq!(resolve(&mut term, "/projects/"), Some(vec!["main", "projects"]));
+    assert_eq!(resolve(&mut term, ".."), Some(vec!["main", "projects"]));
+    assert_eq!(resolve(&mut term, "../.."), Some(vec!["main"]));
+    assert_eq!(resolve(&mut term, "./../app"), Some(vec!["main", "projects", "app"]));
+    assert_eq!(resolve(&mut term, "/.."), None);
+    assert_eq!(resolve(&mut term, "../../.."), None);
+    assert_eq!(resolve(&mut term, "/projects//app"), None);
+    assert_eq!(resolve(&mut term, "missing"), None);
+}
+
+#[test_case]
+fn test_run_pipeline() {
+    let root = test_tree();
+    let cwd = ["main"];
+    assert_eq!(run_pipeline(&root, &cwd, "peek notes.txt | sort -u"), Ok("apple\nfig\npear\n".to_string()));
+    assert_eq!(run_pipeline(&root, &cwd, "peek notes.txt | seek ^a | head 1"), Ok("apple\n".to_string()));
+    assert_eq!(run_pipeline(&root, &cwd, "sort -r notes.txt | tail 2"), Ok("apple\napple\n".to_string()));
+    assert_eq!(run_pipeline(&root, &cwd, "peek notes.txt | halt"), Err("'halt' can't be used in a pipe\n".to_string()));
+    assert_eq!(run_pipeline(&root, &cwd, "peek notes.txt | | wc"), Err("Empty command in pipe\n".to_string()));
+}
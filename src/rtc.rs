@@ -0,0 +1,115 @@
+use core::fmt;
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+/// CMOS register select port
+const CMOS_ADDRESS: u16 = 0x70;
+/// CMOS data port
+const CMOS_DATA: u16 = 0x71;
+
+const REG_SECONDS: u8 = 0x00;
+const REG_MINUTES: u8 = 0x02;
+const REG_HOURS: u8 = 0x04;
+const REG_DAY: u8 = 0x07;
+const REG_MONTH: u8 = 0x08;
+const REG_YEAR: u8 = 0x09;
+const REG_STATUS_A: u8 = 0x0A;
+const REG_STATUS_B: u8 = 0x0B;
+
+/// Wall-clock date and time as reported by the real-time clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+fn read_register(reg: u8) -> u8 {
+    let mut address: Port<u8> = Port::new(CMOS_ADDRESS);
+    let mut data: Port<u8> = Port::new(CMOS_DATA);
+    unsafe {
+        address.write(reg);
+        data.read()
+    }
+}
+
+/// The RTC sets bit 7 of status register A while it updates its registers
+fn update_in_progress() -> bool {
+    read_register(REG_STATUS_A) & 0x80 != 0
+}
+
+fn read_raw() -> [u8; 6] {
+    while update_in_progress() {}
+    [
+        read_register(REG_SECONDS),
+        read_register(REG_MINUTES),
+        read_register(REG_HOURS),
+        read_register(REG_DAY),
+        read_register(REG_MONTH),
+        read_register(REG_YEAR),
+    ]
+}
+
+fn bcd_to_binary(value: u8) -> u8 {
+    (value & 0x0F) + (value >> 4) * 10
+}
+
+/// Read the current date and time from the CMOS RTC
+pub fn read_rtc() -> DateTime {
+    let (raw, status_b) = interrupts::without_interrupts(|| {
+        // Read until two consecutive reads agree so an update can't tear the value
+        let mut last = read_raw();
+        loop {
+            let next = read_raw();
+            if next == last {
+                break;
+            }
+            last = next;
+        }
+        (last, read_register(REG_STATUS_B))
+    });
+
+    let [mut second, mut minute, hour_raw, mut day, mut month, mut year] = raw;
+    let pm = hour_raw & 0x80 != 0;
+    let mut hour = hour_raw & 0x7F;
+
+    // Bit 2 of status register B is set when values are binary instead of BCD
+    if status_b & 0x04 == 0 {
+        second = bcd_to_binary(second);
+        minute = bcd_to_binary(minute);
+        hour = bcd_to_binary(hour);
+        day = bcd_to_binary(day);
+        month = bcd_to_binary(month);
+        year = bcd_to_binary(year);
+    }
+
+    // Bit 1 of status register B is set in 24 hour mode
+    if status_b & 0x02 == 0 {
+        hour %= 12;
+        if pm {
+            hour += 12;
+        }
+    }
+
+    DateTime {
+        year: 2000 + year as u16,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+    }
+}
@@ -1,8 +1,18 @@
-use core::sync::atomic::{AtomicU64, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use x86_64::instructions::port::Port;
 use crate::println;
 use crate::task::executor::EXECUTOR;
-use alloc::format;
+use crate::fs::commands::format_size;
+use alloc::{format, string::String, vec::Vec};
+
+/// Kernel version shown in the boot banner and by `info`
+pub const VERSION: &str = "0.1";
+
+/// Build time in UTC, set by build.rs
+const BUILD_TIME: &str = env!("KATALYST_BUILD_TIME");
+
+/// Optional subsystems built into this kernel, listed by `info`
+const FEATURES: &[&str] = &["in-memory fs", "trash", "pipes", "clipboard", "at scheduler", "background tasks"];
 
 /// Total ticks since boot
 pub static UPTIME_TICKS: AtomicU64 = AtomicU64::new(0);
@@ -10,8 +20,15 @@ pub static UPTIME_TICKS: AtomicU64 = AtomicU64::new(0);
 /// Ticks spent idling (hlt)
 pub static IDLE_TICKS: AtomicU64 = AtomicU64::new(0);
 
-/// Timer frequency: how many times the timer fires per second
-pub const TICKS_PER_SECOND: u64 = 1000;
+/// Timer frequency: how many times the timer fires per second.
+///
+/// Starts at the PIT's power-on rate (about 18 Hz) until `init_timer` runs.
+pub static TICKS_PER_SECOND: AtomicU64 = AtomicU64::new(18);
+
+/// Input clock of the programmable interval timer
+const PIT_BASE_HZ: u64 = 1_193_182;
+/// Tick rate requested from the PIT
+const TARGET_TICKS_PER_SECOND: u64 = 1000;
 
 use crate::repl::Terminal;
 
@@ -30,10 +47,52 @@ pub fn halt(term: &mut Terminal) -> ! {
     
 }
 
+/// QEMU's ACPI PM1a control port and the value that requests S5 (soft off)
+const QEMU_ACPI_PORT: u16 = 0x604;
+const QEMU_ACPI_SHUTDOWN: u16 = 0x2000;
+
+/// Power off via the QEMU ACPI port, halting instead if that has no effect
+pub fn shutdown(term: &mut Terminal) -> ! {
+    term.write_str("Powering off...\n");
+    unsafe {
+        let mut port = Port::new(QEMU_ACPI_PORT);
+        port.write(QEMU_ACPI_SHUTDOWN);
+    }
+
+    // Still running: not on QEMU (or ACPI is disabled)
+    term.write_str("Power off not supported, system halted.\n");
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+/// Program PIT channel 0 to fire at `TARGET_TICKS_PER_SECOND` and record
+/// the rate it actually runs at with the chosen divisor
+pub fn init_timer() {
+    let divisor = (PIT_BASE_HZ + TARGET_TICKS_PER_SECOND / 2) / TARGET_TICKS_PER_SECOND;
+    let mut command: Port<u8> = Port::new(0x43);
+    let mut channel0: Port<u8> = Port::new(0x40);
+    unsafe {
+        command.write(0x36); // channel 0, low then high byte, mode 3 (square wave)
+        channel0.write((divisor & 0xFF) as u8);
+        channel0.write((divisor >> 8) as u8);
+    }
+    TICKS_PER_SECOND.store((PIT_BASE_HZ + divisor / 2) / divisor, Ordering::Relaxed);
+}
+
+/// Current timer frequency in ticks per second
+pub fn ticks_per_second() -> u64 {
+    TICKS_PER_SECOND.load(Ordering::Relaxed)
+}
+
 /// Get uptime in hours, minutes, and seconds
 pub fn get_uptime() -> (u64, u64, u64) {
-    let ticks = UPTIME_TICKS.load(Ordering::Relaxed);
-    let total_seconds = ticks / TICKS_PER_SECOND;
+    ticks_to_hms(UPTIME_TICKS.load(Ordering::Relaxed))
+}
+
+/// Convert a tick count into hours, minutes, and seconds
+pub fn ticks_to_hms(ticks: u64) -> (u64, u64, u64) {
+    let total_seconds = ticks / ticks_per_second();
     let hours = total_seconds / 3600;
     let minutes = (total_seconds % 3600) / 60;
     let seconds = total_seconds % 60;
@@ -73,6 +132,11 @@ pub fn get_cpu_temperature() -> u8 {
 /// Reboot the system via the keyboard controller
 pub fn reboot(term: &mut Terminal) -> ! {
     term.write_str("System rebooting...\n");
+    reset_cpu()
+}
+
+/// Pulse the CPU reset line through the keyboard controller
+fn reset_cpu() -> ! {
     unsafe {
         let mut port = Port::new(0x64);
         port.write(0xFEu8); // pulse reset line
@@ -81,9 +145,148 @@ pub fn reboot(term: &mut Terminal) -> ! {
     loop {
         x86_64::instructions::hlt();
     }
+}
 
-    
+/// Set once the panic screen is up, so a panic inside it just halts
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// Show a panic on a cleared screen and reboot on the next key press.
+///
+/// Interrupts stay off, so the keyboard is polled directly. The VGA writer
+/// is force-unlocked because the panic may have happened while it was held.
+pub fn panic_screen(info: &core::panic::PanicInfo) -> ! {
+    use crate::vga_buffer::{Color, ColorCode, WRITER};
+    use core::fmt::Write;
+
+    x86_64::instructions::interrupts::disable();
+    if PANICKING.swap(true, Ordering::SeqCst) {
+        crate::hlt_loop();
+    }
+
+    unsafe { WRITER.force_unlock() };
+    let mut writer = WRITER.lock();
+    writer.set_color(ColorCode::new(Color::White, Color::Red));
+    writer.clear_screen();
+    let _ = writeln!(writer, "*** KERNEL PANIC ***\n");
+    let _ = writeln!(writer, "{}", info.message());
+    if let Some(location) = info.location() {
+        let _ = writeln!(writer, "at {}:{}:{}", location.file(), location.line(), location.column());
+    }
+    let (h, m, s) = get_uptime();
+    let _ = writeln!(writer, "uptime {:02}:{:02}:{:02}\n", h, m, s);
+    let _ = write!(writer, "Press any key to reboot.");
 
+    let mut status: Port<u8> = Port::new(0x64);
+    let mut data: Port<u8> = Port::new(0x60);
+    unsafe {
+        // Drop scancodes that were already waiting, then wait for a key press
+        while status.read() & 1 != 0 {
+            data.read();
+        }
+        loop {
+            if status.read() & 1 != 0 && data.read() & 0x80 == 0 {
+                break;
+            }
+            core::hint::spin_loop();
+        }
+    }
+    reset_cpu()
+}
+
+/// Run the `cpuid` instruction for `leaf`
+fn cpuid(leaf: u32) -> core::arch::x86_64::CpuidResult {
+    core::arch::x86_64::__cpuid(leaf)
+}
+
+/// Append the little-endian bytes of each register as ASCII
+fn push_register_chars(out: &mut String, registers: &[u32]) {
+    for reg in registers {
+        for byte in reg.to_le_bytes() {
+            if byte != 0 {
+                out.push(byte as char);
+            }
+        }
+    }
+}
+
+/// CPU vendor string from leaf 0, e.g. `GenuineIntel` or `AuthenticAMD`
+pub fn cpu_vendor() -> String {
+    let r = cpuid(0);
+    let mut vendor = String::new();
+    push_register_chars(&mut vendor, &[r.ebx, r.edx, r.ecx]);
+    vendor
+}
+
+/// Processor brand string from leaves 0x80000002-0x80000004, if supported
+pub fn cpu_brand() -> Option<String> {
+    if cpuid(0x8000_0000).eax < 0x8000_0004 {
+        return None;
+    }
+    let mut brand = String::new();
+    for leaf in 0x8000_0002..=0x8000_0004 {
+        let r = cpuid(leaf);
+        push_register_chars(&mut brand, &[r.eax, r.ebx, r.ecx, r.edx]);
+    }
+    Some(brand.trim().into())
+}
+
+/// Notable feature flags reported by leaf 1
+pub fn cpu_features() -> Vec<&'static str> {
+    let r = cpuid(1);
+    let flags = [
+        (r.edx, 9, "APIC"),
+        (r.edx, 25, "SSE"),
+        (r.edx, 26, "SSE2"),
+        (r.ecx, 0, "SSE3"),
+        (r.ecx, 19, "SSE4.1"),
+        (r.ecx, 20, "SSE4.2"),
+        (r.ecx, 21, "x2APIC"),
+        (r.ecx, 28, "AVX"),
+        (r.ecx, 31, "hypervisor"),
+    ];
+    flags.iter().filter(|(reg, bit, _)| reg & (1 << bit) != 0).map(|(_, _, name)| *name).collect()
+}
+
+/// Prints the version, build details and a short boot summary
+pub fn info(term: &mut Terminal) {
+    let (hours, mins, secs) = get_uptime();
+    let profile = if cfg!(debug_assertions) { "debug" } else { "release" };
+    term.write_str(&format!("katalyst v{} ({} {})\n", VERSION, env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")));
+    term.write_str(&format!("Built: {} ({})\n", BUILD_TIME, profile));
+    term.write_str(&format!("Timer: {} Hz, heap {}\n", ticks_per_second(), format_size(crate::allocator::HEAP_SIZE)));
+    term.write_str(&format!("Features: {}\n", FEATURES.join(", ")));
+    term.write_str(&format!("Uptime: {:02}:{:02}:{:02}\n", hours, mins, secs));
+}
+
+/// Prints the bootloader's memory map and physical frame and heap usage
+pub fn meminfo(term: &mut Terminal) {
+    let map = match crate::memory::memory_map() {
+        Some(map) => map,
+        None => {
+            term.write_str("Memory map not available\n");
+            return;
+        }
+    };
+
+    term.write_str("Start               End                 Size      Type\n");
+    for region in map.iter() {
+        let (start, end) = (region.range.start_addr(), region.range.end_addr());
+        term.write_str(&format!(
+            "{:#018x}  {:#018x}  {:>8}  {:?}\n",
+            start, end, format_size((end - start) as usize), region.region_type
+        ));
+    }
+
+    let (used, usable) = crate::memory::frame_stats();
+    term.write_str(&format!(
+        "Usable: {} in {} frames, {} allocated, {} free\n",
+        format_size(usable * 4096), usable, used, usable.saturating_sub(used)
+    ));
+    let (heap_used, heap_peak, heap_total) = crate::allocator::heap_stats();
+    term.write_str(&format!(
+        "Heap: {} used / {} total (peak {})\n",
+        format_size(heap_used), format_size(heap_total), format_size(heap_peak)
+    ));
 }
 
 /// Prints a system core diagnostics report
@@ -95,7 +298,16 @@ pub fn core_report(term: &mut Terminal) {
     term.write_str("=== Core System Report ===\n");
     term.write_str(&format!("Uptime: {:02}:{:02}:{:02}\n", hours, mins, secs));
     term.write_str(&format!("CPU Usage: {}%\n", cpu_usage));
+    term.write_str(&format!("Timer: {} Hz\n", ticks_per_second()));
     term.write_str(&format!("CPU Temperature: {}°C\n", cpu_temp));
+    term.write_str(&format!("CPU: {} ({})\n", cpu_brand().unwrap_or_else(|| "unknown model".into()), cpu_vendor()));
+    term.write_str(&format!("CPU Features: {}\n", cpu_features().join(" ")));
+
+    let (heap_used, heap_peak, heap_total) = crate::allocator::heap_stats();
+    term.write_str(&format!(
+        "Heap: {} used / {} total (peak {})\n",
+        format_size(heap_used), format_size(heap_total), format_size(heap_peak)
+    ));
     term.write_str("Active Tasks:\n");
 
     let exec = EXECUTOR.lock();
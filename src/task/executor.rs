@@ -9,6 +9,7 @@ pub struct Executor {
     tasks: BTreeMap<TaskId, Task>,
     task_queue: Arc<ArrayQueue<TaskId>>,
     waker_cache: BTreeMap<TaskId, Waker>,
+    running: Option<TaskId>, // task currently being polled by `run_global`
 }
 
 impl Executor {
@@ -17,6 +18,7 @@ impl Executor {
             tasks: BTreeMap::new(),
             task_queue: Arc::new(ArrayQueue::new(100)),
             waker_cache: BTreeMap::new(),
+            running: None,
         }
     }
 
@@ -40,6 +42,7 @@ impl Executor {
             tasks,
             task_queue,
             waker_cache,
+            ..
         } = self;
 
         while let Some(task_id) = task_queue.pop() {
@@ -64,24 +67,92 @@ impl Executor {
     }
 
     fn sleep_if_idle(&self) {
-        use x86_64::instructions::interrupts::{self, enable_and_hlt};
-
-        interrupts::disable();
-        if self.task_queue.is_empty() {
-            enable_and_hlt();
-        } else {
-            interrupts::enable();
-        }
+        sleep_if_queue_empty(&self.task_queue);
     }
 
     /// Returns number of active tasks
     pub fn task_count(&self) -> usize {
-        self.tasks.len()
+        self.tasks.len() + self.running.iter().count()
     }
 
     /// Returns an iterator over active task IDs
     pub fn task_ids(&self) -> impl Iterator<Item = &super::TaskId> {
-        self.tasks.keys()
+        self.tasks.keys().chain(self.running.iter())
+    }
+
+    /// Returns the task currently being polled, if any
+    pub fn current_task(&self) -> Option<TaskId> {
+        self.running
+    }
+
+    /// Cancel a task, dropping its future and cached waker.
+    ///
+    /// Returns `false` if the task doesn't exist or is the one currently
+    /// running (a task can't cancel itself mid-poll).
+    pub fn kill(&mut self, task_id: TaskId) -> bool {
+        if self.running == Some(task_id) {
+            return false;
+        }
+        self.waker_cache.remove(&task_id);
+        self.tasks.remove(&task_id).is_some()
+    }
+}
+
+/// Run the global `EXECUTOR` forever.
+///
+/// Unlike `Executor::run`, the lock is only held between polls, so running
+/// tasks can inspect and modify the executor themselves (`core`, `kill`).
+pub fn run_global() -> ! {
+    let task_queue = EXECUTOR.lock().task_queue.clone();
+    loop {
+        run_ready_global(&task_queue);
+        sleep_if_queue_empty(&task_queue);
+    }
+}
+
+fn run_ready_global(task_queue: &ArrayQueue<TaskId>) {
+    while let Some(task_id) = task_queue.pop() {
+        // Take the task out so the lock is released while it runs
+        let (mut task, waker) = {
+            let mut exec = EXECUTOR.lock();
+            let task = match exec.tasks.remove(&task_id) {
+                Some(task) => task,
+                None => continue, // finished or killed
+            };
+            let queue = exec.task_queue.clone();
+            let waker = exec
+                .waker_cache
+                .entry(task_id)
+                .or_insert_with(|| TaskWaker::new(task_id, queue))
+                .clone();
+            exec.running = Some(task_id);
+            (task, waker)
+        };
+
+        let mut context = Context::from_waker(&waker);
+        let result = task.poll(&mut context);
+
+        let mut exec = EXECUTOR.lock();
+        exec.running = None;
+        match result {
+            Poll::Ready(()) => {
+                exec.waker_cache.remove(&task_id);
+            }
+            Poll::Pending => {
+                exec.tasks.insert(task_id, task);
+            }
+        }
+    }
+}
+
+fn sleep_if_queue_empty(task_queue: &ArrayQueue<TaskId>) {
+    use x86_64::instructions::interrupts::{self, enable_and_hlt};
+
+    interrupts::disable();
+    if task_queue.is_empty() {
+        enable_and_hlt();
+    } else {
+        interrupts::enable();
     }
 }
 
@@ -1,5 +1,6 @@
 pub mod executor;
 pub mod keyboard;
+pub mod timer;
 
 
 use alloc::boxed::Box;
@@ -26,6 +27,10 @@ impl Task {
         }
     }
 
+    pub fn id(&self) -> TaskId {
+        self.id
+    }
+
     pub fn poll(&mut self, context: &mut Context<'_>) -> Poll<()> {
         self.future.as_mut().poll(context)
     }
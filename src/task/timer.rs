@@ -0,0 +1,111 @@
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    task::{Context, Poll},
+};
+use futures_util::task::AtomicWaker;
+
+use crate::sys::{ticks_per_second, UPTIME_TICKS};
+
+/// Number of tasks that can sleep on the timer at the same time
+const MAX_SLEEPERS: usize = 16;
+
+/// A sleeping task's deadline and waker
+struct Slot {
+    in_use: AtomicBool,
+    deadline: AtomicU64, // 0 while nothing is waiting
+    waker: AtomicWaker,
+}
+
+static SLOTS: [Slot; MAX_SLEEPERS] = [const {
+    Slot {
+        in_use: AtomicBool::new(false),
+        deadline: AtomicU64::new(0),
+        waker: AtomicWaker::new(),
+    }
+}; MAX_SLEEPERS];
+
+/// Called by the timer interrupt handler to wake sleepers whose deadline passed
+///
+/// Must not block or allocate.
+pub(crate) fn on_tick(now: u64) {
+    for slot in SLOTS.iter() {
+        let deadline = slot.deadline.load(Ordering::SeqCst);
+        if deadline != 0 && deadline <= now {
+            slot.deadline.store(0, Ordering::SeqCst);
+            slot.waker.wake();
+        }
+    }
+}
+
+/// Future that completes once `UPTIME_TICKS` reaches a deadline
+pub struct Sleep {
+    deadline: u64,
+    slot: Option<usize>,
+}
+
+/// Sleep for at least `ticks` timer ticks
+pub fn sleep_ticks(ticks: u64) -> Sleep {
+    Sleep {
+        deadline: UPTIME_TICKS.load(Ordering::SeqCst) + ticks.max(1),
+        slot: None,
+    }
+}
+
+/// Sleep until `UPTIME_TICKS` reaches `deadline`
+pub fn sleep_until(deadline: u64) -> Sleep {
+    Sleep { deadline, slot: None }
+}
+
+/// Sleep for at least `ms` milliseconds
+pub fn sleep_ms(ms: u64) -> Sleep {
+    sleep_ticks(ms * ticks_per_second() / 1000)
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        if UPTIME_TICKS.load(Ordering::SeqCst) >= self.deadline {
+            return Poll::Ready(());
+        }
+
+        let slot = match self.slot.or_else(claim_slot) {
+            Some(slot) => slot,
+            None => {
+                // All slots taken: fall back to polling again right away
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+        };
+        self.slot = Some(slot);
+        SLOTS[slot].waker.register(cx.waker());
+        SLOTS[slot].deadline.store(self.deadline, Ordering::SeqCst);
+
+        // A tick may have passed the deadline before the waker was registered
+        if UPTIME_TICKS.load(Ordering::SeqCst) >= self.deadline {
+            SLOTS[slot].deadline.store(0, Ordering::SeqCst);
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            SLOTS[slot].deadline.store(0, Ordering::SeqCst);
+            SLOTS[slot].in_use.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+fn claim_slot() -> Option<usize> {
+    SLOTS.iter().position(|slot| {
+        slot.in_use
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_ok()
+    })
+}
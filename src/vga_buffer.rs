@@ -40,13 +40,18 @@ pub enum Color {
 /// A combination of a foreground and a background color.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
-struct ColorCode(u8);
+pub struct ColorCode(u8);
 
 impl ColorCode {
     /// Create a new `ColorCode` with the given foreground and background colors.
-    fn new(foreground: Color, background: Color) -> ColorCode {
+    pub const fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    /// The raw VGA attribute byte.
+    pub fn as_u8(self) -> u8 {
+        self.0
+    }
 }
 
 /// A screen character in the VGA text buffer, consisting of an ASCII character and a `ColorCode`.
@@ -103,22 +108,36 @@ impl Writer {
         }
     }
 
-    /// Writes the given ASCII string to the buffer.
+    /// Writes the given string to the buffer.
     ///
-    /// Wraps lines at `BUFFER_WIDTH`. Supports the `\n` newline character. Does **not**
-    /// support strings with non-ASCII characters, since they can't be printed in the VGA text
-    /// mode.
+    /// Wraps lines at `BUFFER_WIDTH`. Supports the `\n` newline character. Characters
+    /// outside printable ASCII are translated with `to_cp437`.
     fn write_string(&mut self, s: &str) {
-        for byte in s.bytes() {
-            match byte {
-                // printable ASCII byte or newline
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                // not part of printable ASCII range
-                _ => self.write_byte(0xfe),
+        for c in s.chars() {
+            match c {
+                '\n' => self.write_byte(b'\n'),
+                c => {
+                    if let Some(byte) = to_cp437(c) {
+                        self.write_byte(byte);
+                    }
+                }
             }
         }
     }
 
+    /// Sets the color used for characters written from now on.
+    pub fn set_color(&mut self, color_code: ColorCode) {
+        self.color_code = color_code;
+    }
+
+    /// Blanks the whole screen in the current color and restarts the bottom row.
+    pub fn clear_screen(&mut self) {
+        for row in 0..BUFFER_HEIGHT {
+            self.clear_row(row);
+        }
+        self.column_position = 0;
+    }
+
     /// Shifts all lines one line up and clears the last row.
     fn new_line(&mut self) {
         for row in 1..BUFFER_HEIGHT {
@@ -150,6 +169,126 @@ impl fmt::Write for Writer {
     }
 }
 
+/// Translate a character to its glyph in code page 437, the VGA hardware font.
+///
+/// Printable ASCII maps to itself; common symbols, accented letters and
+/// box-drawing characters map to their CP437 equivalents. A tab shows as a
+/// space and `\r` (from CRLF line endings) as nothing, i.e. `None`.
+/// Anything else becomes `?`.
+pub fn to_cp437(c: char) -> Option<u8> {
+    match c {
+        '\r' => None,
+        '\t' => Some(b' '),
+        c => Some(cp437_glyph(c)),
+    }
+}
+
+/// The CP437 glyph for a printable character, or `?`
+fn cp437_glyph(c: char) -> u8 {
+    match c {
+        ' '..='~' => c as u8,
+        // symbols
+        '☺' => 0x01,
+        '♥' => 0x03,
+        '♦' => 0x04,
+        '♣' => 0x05,
+        '♠' => 0x06,
+        '•' => 0x07,
+        '►' => 0x10,
+        '◄' => 0x11,
+        '↑' => 0x18,
+        '↓' => 0x19,
+        '→' => 0x1a,
+        '←' => 0x1b,
+        '▲' => 0x1e,
+        '▼' => 0x1f,
+        // accented letters and currency
+        'Ç' => 0x80,
+        'ü' => 0x81,
+        'é' => 0x82,
+        'â' => 0x83,
+        'ä' => 0x84,
+        'à' => 0x85,
+        'å' => 0x86,
+        'ç' => 0x87,
+        'ê' => 0x88,
+        'ë' => 0x89,
+        'è' => 0x8a,
+        'ï' => 0x8b,
+        'î' => 0x8c,
+        'ì' => 0x8d,
+        'Ä' => 0x8e,
+        'Å' => 0x8f,
+        'É' => 0x90,
+        'ô' => 0x93,
+        'ö' => 0x94,
+        'ò' => 0x95,
+        'û' => 0x96,
+        'ù' => 0x97,
+        'Ö' => 0x99,
+        'Ü' => 0x9a,
+        '¢' => 0x9b,
+        '£' => 0x9c,
+        '¥' => 0x9d,
+        'á' => 0xa0,
+        'í' => 0xa1,
+        'ó' => 0xa2,
+        'ú' => 0xa3,
+        'ñ' => 0xa4,
+        'Ñ' => 0xa5,
+        '¿' => 0xa8,
+        '¡' => 0xad,
+        '«' => 0xae,
+        '»' => 0xaf,
+        // shades and box drawing
+        '░' => 0xb0,
+        '▒' => 0xb1,
+        '▓' => 0xb2,
+        '│' => 0xb3,
+        '┤' => 0xb4,
+        '╣' => 0xb9,
+        '║' => 0xba,
+        '╗' => 0xbb,
+        '╝' => 0xbc,
+        '┐' => 0xbf,
+        '└' => 0xc0,
+        '┴' => 0xc1,
+        '┬' => 0xc2,
+        '├' => 0xc3,
+        '─' => 0xc4,
+        '┼' => 0xc5,
+        '╚' => 0xc8,
+        '╔' => 0xc9,
+        '╩' => 0xca,
+        '╦' => 0xcb,
+        '╠' => 0xcc,
+        '═' => 0xcd,
+        '╬' => 0xce,
+        '┘' => 0xd9,
+        '┌' => 0xda,
+        '█' => 0xdb,
+        '▄' => 0xdc,
+        '▀' => 0xdf,
+        // math and greek
+        'ß' => 0xe1,
+        'π' => 0xe3,
+        'µ' => 0xe6,
+        '∞' => 0xec,
+        '≡' => 0xf0,
+        '±' => 0xf1,
+        '≥' => 0xf2,
+        '≤' => 0xf3,
+        '÷' => 0xf6,
+        '≈' => 0xf7,
+        '°' => 0xf8,
+        '·' => 0xfa,
+        '√' => 0xfb,
+        '²' => 0xfd,
+        '■' => 0xfe,
+        _ => b'?',
+    }
+}
+
 /// Like the `print!` macro in the standard library, but prints to the VGA text buffer.
 #[macro_export]
 macro_rules! print {
@@ -202,3 +341,12 @@ fn test_println_output() {
         }
     });
 }
+
+#[test_case]
+fn test_to_cp437() {
+    assert_eq!(to_cp437('A'), Some(b'A'));
+    assert_eq!(to_cp437('°'), Some(0xf8));
+    assert_eq!(to_cp437('€'), Some(b'?'));
+    assert_eq!(to_cp437('\t'), Some(b' '));
+    assert_eq!(to_cp437('\r'), None);
+}